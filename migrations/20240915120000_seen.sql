@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS seen
+(
+    flow        TEXT                NOT NULL,
+    node        INTEGER             NOT NULL,
+    id          TEXT                NOT NULL,
+    last_seen   DATETIME            NOT NULL,
+
+    PRIMARY KEY (flow, node, id)
+);
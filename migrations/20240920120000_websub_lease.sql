@@ -0,0 +1,5 @@
+ALTER TABLE websub ADD COLUMN state TEXT NOT NULL DEFAULT 'requested';
+ALTER TABLE websub ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE websub ADD COLUMN last_error TEXT;
+-- When the subscription is next (re)subscribed: a retry, a renewal, or a verification that never came.
+ALTER TABLE websub ADD COLUMN next_attempt DATETIME;
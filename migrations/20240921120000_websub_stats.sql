@@ -0,0 +1,3 @@
+ALTER TABLE websub ADD COLUMN accepted INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE websub ADD COLUMN unsigned INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE websub ADD COLUMN bad_signature INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS hub_subscriptions
+(
+    callback    TEXT                NOT NULL,
+    topic       TEXT                NOT NULL,
+    flow        TEXT                NOT NULL,
+    output      TEXT                ,
+    secret      TEXT                ,
+    lease_end   DATETIME            NOT NULL,
+
+    PRIMARY KEY (callback, topic)
+);
@@ -0,0 +1,13 @@
+-- Validators and body of the last full response of each `Feed` node, for conditional GETs.
+CREATE TABLE IF NOT EXISTS feed_cache
+(
+    flow            TEXT                NOT NULL,
+    node            INTEGER             NOT NULL,
+    url             TEXT                NOT NULL,
+    etag            TEXT                ,
+    last_modified   TEXT                ,
+    content_type    TEXT                ,
+    content         BLOB                NOT NULL,
+
+    PRIMARY KEY (flow, node)
+);
@@ -0,0 +1,9 @@
+-- Keys for the management API, stored as SHA-256 hashes.
+CREATE TABLE IF NOT EXISTS api_keys
+(
+    name        TEXT PRIMARY KEY    NOT NULL,
+    hash        BLOB UNIQUE         NOT NULL,
+    scope       TEXT                NOT NULL,
+    created     DATETIME            NOT NULL,
+    last_used   DATETIME
+);
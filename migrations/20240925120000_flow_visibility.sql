@@ -0,0 +1,3 @@
+-- Private flows are only served with their token, stored as a SHA-256 hash.
+ALTER TABLE flows ADD COLUMN visibility TEXT DEFAULT 'public' NOT NULL;
+ALTER TABLE flows ADD COLUMN token_hash BLOB;
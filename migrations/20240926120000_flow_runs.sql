@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS flow_runs
+(
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    flow        TEXT                NOT NULL,
+    trigger     TEXT                NOT NULL,
+    started     DATETIME            NOT NULL,
+    finished    DATETIME            NOT NULL,
+    failed_node INTEGER             ,
+    error       TEXT                ,
+    entries_in  INTEGER             NOT NULL,
+    entries_out INTEGER             NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS flow_runs_flow ON flow_runs (flow, id);
@@ -0,0 +1,2 @@
+-- File of flows loaded from the flows directory, which are read-only in the API.
+ALTER TABLE flows ADD COLUMN file TEXT;
@@ -1,6 +1,18 @@
-use std::{collections::HashMap, ops::Deref, sync::Arc};
+use std::{
+	collections::{HashMap, HashSet},
+	ops::Deref,
+	sync::Arc,
+	time::Instant,
+};
 
-use axum::{extract::FromRef, http::StatusCode, routing::get, Router};
+use axum::{
+	extract::FromRef,
+	http::StatusCode,
+	middleware,
+	routing::{get, post},
+	Router,
+};
+use chrono::Utc;
 use futures::StreamExt;
 use sqlx::{
 	sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
@@ -12,17 +24,46 @@ use tower_http::trace::TraceLayer;
 use url::Url;
 
 use crate::{
-	config::{config, AppConfig},
-	flow::{node::Data, Flow, FlowBuilder},
-	route, subscriber,
+	config::config,
+	flow::{node::Data, Context, Flow, FlowBuilder},
+	flows_dir::{self, FlowsDir},
+	history::{self, Trigger},
+	http::HttpClient,
+	hub::{self, WebSubHub},
+	metrics,
+	route::{self, RenderCache},
+	scheduler, subscriber,
 	subscriber::websub::WebSubSubscriber,
 };
 
+/// What was last sent to subscribers of a flow.
+#[derive(Default)]
+struct Broadcasted {
+	version: Option<u64>,
+	/// Ids of the entries in the last sent result.
+	ids: HashSet<String>,
+}
+
 #[derive(Clone)]
-pub struct FlowHandle(Arc<Flow>, broadcast::Sender<Data>);
+pub struct FlowHandle(
+	Arc<Flow>,
+	broadcast::Sender<Data>,
+	Arc<RenderCache>,
+	Arc<parking_lot::Mutex<Broadcasted>>,
+);
 impl FlowHandle {
 	pub fn new(arc: Arc<Flow>) -> Self {
-		FlowHandle(arc, broadcast::channel(100).0)
+		FlowHandle(
+			arc,
+			broadcast::channel(100).0,
+			Arc::default(),
+			Arc::default(),
+		)
+	}
+
+	/// Rendered outputs, dropped along with the handle when the flow is replaced.
+	pub fn cache(&self) -> &RenderCache {
+		&self.2
 	}
 
 	pub fn tx(&self) -> &broadcast::Sender<Data> {
@@ -32,6 +73,33 @@ impl FlowHandle {
 	pub fn subscribe(&self) -> broadcast::Receiver<Data> {
 		self.1.subscribe()
 	}
+
+	/// Sends the flow's result to subscribers if it changed since last time, feeds one new entry
+	/// at a time, oldest first. Entries of the previous result aren't sent again.
+	pub fn broadcast(&self) {
+		let Some((data, revision)) = self.result_io().and_then(|io| io.snapshot()) else {
+			return;
+		};
+
+		let mut broadcasted = self.3.lock();
+		if broadcasted.version == Some(revision.version) {
+			return;
+		}
+		broadcasted.version = Some(revision.version);
+
+		if let Data::Feed(feed) = data {
+			let ids = feed.entries.iter().map(|e| e.id.clone()).collect();
+			let sent = std::mem::replace(&mut broadcasted.ids, ids);
+			for entry in feed.entries.into_iter().rev() {
+				if !sent.contains(&entry.id) {
+					let _ = self.tx().send(Data::Entry(entry));
+				}
+			}
+		} else {
+			broadcasted.ids.clear();
+			let _ = self.tx().send(data);
+		}
+	}
 }
 
 impl Deref for FlowHandle {
@@ -46,8 +114,10 @@ impl Deref for FlowHandle {
 pub struct AppStateInner {
 	pub flows: Mutex<HashMap<String, FlowHandle>>,
 	pub pool: SqlitePool,
+	pub http: HttpClient,
 
 	pub web_sub_subscriber: WebSubSubscriber,
+	pub web_sub_hub: WebSubHub,
 }
 
 #[derive(Clone)]
@@ -63,69 +133,260 @@ impl Deref for AppState {
 	}
 }
 
+impl AppState {
+	pub fn new(flows: HashMap<String, FlowHandle>, pool: SqlitePool, http: HttpClient) -> Self {
+		AppState(Arc::new(AppStateInner {
+			flows: Mutex::new(flows),
+			web_sub_subscriber: WebSubSubscriber::new(pool.clone(), http.clone()),
+			web_sub_hub: WebSubHub::new(pool.clone(), http.clone()),
+			pool,
+			http,
+		}))
+	}
+
+	/// Runs a flow, recording the run in the flow's history.
+	pub async fn run_flow(
+		&self,
+		name: &str,
+		flow: &FlowHandle,
+		trigger: Trigger,
+	) -> anyhow::Result<()> {
+		let started = Utc::now();
+		let start = Instant::now();
+		let report = flow.execute().await;
+
+		metrics::record_run(name, trigger, start.elapsed(), &report);
+		if let Err(err) = history::record(&self.pool, name, trigger, started, &report).await {
+			tracing::warn!("Recording run of `{name}` flow failed: {err}");
+		}
+
+		report.into_result()
+	}
+
+	/// Serves a built flow, subscribing to the hubs of its feeds. Its row in `flows` must exist.
+	pub async fn install_flow(&self, name: &str, flow: Flow) -> anyhow::Result<()> {
+		if flow.has_subscriptions() {
+			self.web_sub_subscriber.register_flow(name, &flow).await?;
+		}
+
+		self.flows
+			.lock()
+			.await
+			.insert(name.to_string(), FlowHandle::new(Arc::new(flow)));
+		self.web_sub_hub.forget(name);
+
+		Ok(())
+	}
+
+	/// Stops serving a flow and deletes it, along with everything stored for it.
+	pub async fn remove_flow(&self, name: &str) -> anyhow::Result<()> {
+		let flow = self.flows.lock().await.remove(name);
+		self.web_sub_hub.forget(name);
+
+		let mut conn = self.pool.acquire().await?;
+		sqlx::query!("DELETE FROM flows WHERE name = ?", name)
+			.execute(&mut *conn)
+			.await?;
+		sqlx::query!("DELETE FROM seen WHERE flow = ?", name)
+			.execute(&mut *conn)
+			.await?;
+		sqlx::query!("DELETE FROM hub_subscriptions WHERE flow = ?", name)
+			.execute(&mut *conn)
+			.await?;
+		sqlx::query!("DELETE FROM feed_cache WHERE flow = ?", name)
+			.execute(&mut *conn)
+			.await?;
+		sqlx::query!("DELETE FROM flow_runs WHERE flow = ?", name)
+			.execute(&mut *conn)
+			.await?;
+		drop(conn);
+
+		if let Some(flow) = flow {
+			self.web_sub_subscriber.unregister_flow(flow).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Sends the new result of a flow to its SSE subscribers, and to subscribers of our hub.
+	/// Runs that left the result as it was send nothing.
+	pub fn publish(&self, name: &str, flow: &FlowHandle) {
+		flow.broadcast();
+
+		let (state, name, flow) = (self.clone(), name.to_string(), flow.clone());
+		tokio::spawn(async move {
+			if let Err(err) = state.web_sub_hub.publish(&name, &flow).await {
+				tracing::warn!("Publishing `{name}` flow failed: {err}");
+			}
+		});
+	}
+}
+
 impl FromRef<AppState> for SqlitePool {
 	fn from_ref(input: &AppState) -> Self {
 		input.pool.clone()
 	}
 }
 
-fn load_flow(content: &str) -> anyhow::Result<Flow> {
+impl FromRef<AppState> for HttpClient {
+	fn from_ref(input: &AppState) -> Self {
+		input.http.clone()
+	}
+}
+
+fn load_flow(
+	name: &str,
+	content: &str,
+	pool: &SqlitePool,
+	http: &HttpClient,
+) -> anyhow::Result<Flow> {
 	let flow: FlowBuilder = serde_json::de::from_str(content)?;
 
-	Ok(flow.build())
+	Ok(flow
+		.context(Context::new(name, pool.clone(), http.clone()))
+		.build()?)
 }
 
-pub async fn websub_check(public_url: &Url) -> anyhow::Result<()> {
-	let resp = reqwest::get(public_url.join("/websub/check")?).await?;
+pub async fn websub_check(http: &HttpClient, public_url: &Url) -> anyhow::Result<()> {
+	let resp = http
+		.send(http.get(public_url.join("/websub/check")?))
+		.await?;
 
 	resp.error_for_status()?;
 	Ok(())
 }
 
-pub async fn app() -> anyhow::Result<Router> {
-	let config = config().await;
+/// Opens the configured database, migrating it.
+pub async fn connect() -> anyhow::Result<SqlitePool> {
 	let pool = SqlitePoolOptions::new()
 		.connect_with(
 			SqliteConnectOptions::new()
-				.filename(&config.database_file)
+				.filename(&config().await.database_file)
 				.journal_mode(SqliteJournalMode::Wal)
 				.create_if_missing(true),
 		)
 		.await?;
 	sqlx::migrate!().run(&pool).await?;
 
+	Ok(pool)
+}
+
+pub async fn app(http: HttpClient) -> anyhow::Result<Router> {
+	let pool = connect().await?;
+
 	let mut conn = pool.acquire().await?;
 
 	let flows = sqlx::query!("SELECT * FROM flows")
 		.fetch(&mut *conn)
 		.filter_map(|f| async { f.ok() })
-		.filter_map(|record| async move {
-			if let Ok(flow) = load_flow(&record.content).map(Arc::new) {
-				tracing::info!("Loaded `{}` flow", record.name);
-				Some((record.name, flow))
-			} else {
-				tracing::error!("Failed loading `{}` flow", record.name);
-				None
-			}
-		})
-		.map(|(k, v)| (k, FlowHandle::new(v)))
-		.collect()
-		.await;
+		.collect::<Vec<_>>()
+		.await
+		.into_iter()
+		.filter_map(
+			|record| match load_flow(&record.name, &record.content, &pool, &http) {
+				Ok(flow) => {
+					tracing::info!("Loaded `{}` flow", record.name);
+					Some((record.name, FlowHandle::new(Arc::new(flow))))
+				}
+				Err(err) => {
+					tracing::error!("Failed loading `{}` flow: {err}", record.name);
+					None
+				}
+			},
+		)
+		.collect();
+	drop(conn);
+
+	let state = AppState::new(flows, pool, http);
+
+	if let Some(dir) = &config().await.flows_dir {
+		let mut flows_dir = FlowsDir::new(dir.clone());
+		flows_dir.sync(&state).await;
+		tokio::spawn(flows_dir.watch(state.clone()));
+	} else {
+		flows_dir::release(&state.pool).await?;
+	}
+
+	tokio::spawn(scheduler::run(state.clone()));
+	tokio::spawn({
+		let state = state.clone();
+		async move { state.web_sub_subscriber.manage_leases().await }
+	});
 
-	let web_sub_subscriber = WebSubSubscriber::new(pool.clone());
-	let state = AppState(Arc::new(AppStateInner {
-		flows: Mutex::new(flows),
-		pool,
-		web_sub_subscriber,
-	}));
+	route::warn_if_keyless(&state.pool).await;
 
 	let router = Router::new()
-		.nest("/api", route::api())
+		.nest(
+			"/api",
+			route::api().route_layer(middleware::from_fn_with_state(
+				state.clone(),
+				route::require_key,
+			)),
+		)
 		.nest("/flow", route::flow())
 		.route("/", get(|| async { StatusCode::OK }))
 		.nest("/websub", subscriber::websub::router())
+		.route("/hub", post(hub::request))
+		.route("/metrics", get(metrics::metrics))
 		.with_state(state)
 		.layer(ServiceBuilder::new().layer(TraceLayer::new_for_http()));
 
 	Ok(router)
 }
+
+#[cfg(test)]
+mod test {
+	use std::sync::Arc;
+
+	use atom_syndication::{Entry, Feed};
+	use serde_json::json;
+
+	use super::FlowHandle;
+	use crate::flow::{node::Data, FlowBuilder};
+
+	fn feed(ids: &[&str]) -> Data {
+		Data::Feed(Feed {
+			entries: ids
+				.iter()
+				.map(|id| Entry {
+					id: (*id).to_string(),
+					..Entry::default()
+				})
+				.collect(),
+			..Feed::default()
+		})
+	}
+
+	fn sent(rx: &mut tokio::sync::broadcast::Receiver<Data>) -> Vec<String> {
+		std::iter::from_fn(|| match rx.try_recv() {
+			Ok(Data::Entry(entry)) => Some(entry.id),
+			_ => None,
+		})
+		.collect()
+	}
+
+	#[tokio::test]
+	pub async fn broadcast() -> anyhow::Result<()> {
+		let flow: FlowBuilder = serde_json::from_value(json!({
+			"nodes": [{ "type": "Feed", "url": "https://example.com/feed.xml", "ttl": 60 }]
+		}))?;
+		let flow = FlowHandle::new(Arc::new(flow.build()?));
+		let mut rx = flow.subscribe();
+		let result = flow.result_io().expect("flow has a result").clone();
+
+		result.accept(feed(&["b", "a"]))?;
+		flow.broadcast();
+		assert_eq!(sent(&mut rx), ["a", "b"]);
+
+		// An unchanged result is not sent again.
+		result.accept(feed(&["b", "a"]))?;
+		flow.broadcast();
+		assert!(sent(&mut rx).is_empty());
+
+		result.accept(feed(&["c", "b", "a"]))?;
+		flow.broadcast();
+		assert_eq!(sent(&mut rx), ["c"]);
+
+		Ok(())
+	}
+}
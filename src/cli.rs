@@ -0,0 +1,328 @@
+//! Subcommands of the `rssflow` binary besides `serve`, working without a running server.
+
+use std::{
+	collections::BTreeMap,
+	io::{self, Read, Write},
+	path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context as _};
+use clap::{Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{
+	app,
+	config::config,
+	flow::{node::Data, Context, FlowBuilder},
+	flows_dir,
+	http::HttpClient,
+	route::{create_key, Scope, Visibility},
+};
+
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+	#[command(subcommand)]
+	pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+	/// Start the server, the default.
+	Serve,
+	#[command(flatten)]
+	Task(Task),
+}
+
+// Commands run by `run`, everything but serving. Not a doc comment, clap would take it for
+// the description of the binary.
+#[derive(Subcommand)]
+pub enum Task {
+	/// Run a flow file once and print its result as Atom.
+	///
+	/// The flow is isolated like a preview: `Seen` keeps ids in memory and feeds are fetched
+	/// unconditionally.
+	Run {
+		/// A `.json` or `.toml` flow file, like those in `FLOWS_DIR`.
+		file: PathBuf,
+		/// Print this output instead of the result, either by its port (`N3P0`) or its name.
+		#[arg(long)]
+		output: Option<String>,
+	},
+	/// Check flow files without running them.
+	Validate {
+		#[arg(required = true)]
+		files: Vec<PathBuf>,
+	},
+	/// Print all stored flows as a JSON object of flows by name, with their visibility.
+	Export {
+		/// Write to this file instead of stdout.
+		#[arg(short, long)]
+		output: Option<PathBuf>,
+	},
+	/// Store flows from a JSON object of flows by name, as printed by `export`.
+	///
+	/// A `.toml` file is read as TOML instead, like flow files in `FLOWS_DIR`.
+	///
+	/// Existing flows are replaced, along with their visibility and token. A running server
+	/// picks the flows up when restarted.
+	Import {
+		/// Read from this file instead of stdin.
+		file: Option<PathBuf>,
+	},
+	/// Create an API key and print it, the only time it is shown.
+	///
+	/// Without a key in `API_KEYS` or created here, the API rejects every request.
+	Key {
+		name: String,
+		/// Only allow reading.
+		#[arg(long)]
+		read_only: bool,
+	},
+}
+
+async fn read_flow(file: &Path) -> anyhow::Result<FlowBuilder> {
+	flows_dir::parse(file)
+		.await
+		.with_context(|| format!("Reading `{}` failed", file.display()))
+}
+
+pub async fn run(task: Task) -> anyhow::Result<()> {
+	match task {
+		Task::Run { file, output } => {
+			let flow = read_flow(&file)
+				.await?
+				.context(Context {
+					http: HttpClient::new(&config().await.http)?,
+					..Context::default()
+				})
+				.build()?;
+			flow.execute().await.into_result()?;
+
+			let data = match &output {
+				Some(name) => flow.output(name),
+				None => flow.result(),
+			};
+			let Some(Data::Feed(feed)) = data else {
+				bail!("The flow produced no feed");
+			};
+
+			let mut stdout = feed.write_to(io::stdout().lock())?;
+			writeln!(stdout)?;
+		}
+		Task::Validate { files } => {
+			let mut invalid = 0;
+			for file in &files {
+				if let Err(err) = validate(file).await {
+					invalid += 1;
+					eprintln!("{}: {err:#}", file.display());
+				}
+			}
+
+			if invalid > 0 {
+				bail!("{invalid} of {} flows are invalid", files.len());
+			}
+		}
+		Task::Export { output } => {
+			let flows = export(&app::connect().await?).await?;
+			let json = serde_json::to_string_pretty(&flows)?;
+
+			match output {
+				Some(path) => std::fs::write(path, json)?,
+				None => println!("{json}"),
+			}
+		}
+		Task::Import { file } => {
+			let flows = if let Some(path) = file {
+				flows_dir::parse(&path)
+					.await
+					.with_context(|| format!("Reading `{}` failed", path.display()))?
+			} else {
+				let mut json = String::new();
+				io::stdin().read_to_string(&mut json)?;
+				serde_json::from_str(&json)?
+			};
+
+			let count = import(&app::connect().await?, flows).await?;
+			eprintln!("Imported {count} flows");
+		}
+		Task::Key { name, read_only } => {
+			let scope = if read_only { Scope::Read } else { Scope::Write };
+			let Some(key) = create_key(&app::connect().await?, &name, scope).await? else {
+				bail!("A key named `{name}` already exists");
+			};
+
+			println!("{key}");
+		}
+	}
+
+	Ok(())
+}
+
+async fn validate(file: &Path) -> anyhow::Result<()> {
+	read_flow(file).await?.build()?;
+	Ok(())
+}
+
+/// A stored flow as exported, its token only as the hash stored.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Exported<F> {
+	flow: F,
+	#[serde(default)]
+	visibility: Visibility,
+	/// Hex encoded.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	token_hash: Option<String>,
+}
+
+/// Stored flows by name, as stored, even those failing to load.
+async fn export(
+	pool: &SqlitePool,
+) -> anyhow::Result<BTreeMap<String, Exported<serde_json::Value>>> {
+	sqlx::query!(
+		r#"SELECT name, content, visibility as "visibility: Visibility", token_hash FROM flows"#
+	)
+	.fetch_all(pool)
+	.await?
+	.into_iter()
+	.map(|r| {
+		let flow = serde_json::from_str(&r.content)
+			.with_context(|| format!("Stored `{}` flow isn't JSON", r.name))?;
+		let exported = Exported {
+			flow,
+			visibility: r.visibility,
+			token_hash: r.token_hash.map(hex::encode),
+		};
+		Ok((r.name, exported))
+	})
+	.collect()
+}
+
+/// Stores all flows if every one of them is valid, returning how many there were.
+async fn import(
+	pool: &SqlitePool,
+	flows: BTreeMap<String, Exported<FlowBuilder>>,
+) -> anyhow::Result<usize> {
+	let mut validated = Vec::with_capacity(flows.len());
+	let mut problems = Vec::new();
+	for (name, exported) in flows {
+		let file = sqlx::query_scalar!("SELECT file FROM flows WHERE name = ?", name)
+			.fetch_optional(pool)
+			.await?
+			.flatten();
+		if let Some(file) = file {
+			problems.push(format!("`{name}`: loaded from `{file}` and read-only"));
+			continue;
+		}
+
+		let token_hash = match exported.token_hash.as_deref().map(hex::decode).transpose() {
+			Ok(token_hash) => token_hash,
+			Err(err) => {
+				problems.push(format!("`{name}`: invalid token hash: {err}"));
+				continue;
+			}
+		};
+		let json = serde_json::to_string(&exported.flow)?;
+		match exported.flow.build() {
+			Ok(_) => validated.push((name, json, exported.visibility, token_hash)),
+			Err(err) => problems.push(format!("`{name}`: {err}")),
+		}
+	}
+
+	if !problems.is_empty() {
+		return Err(anyhow!(problems.join("\n")).context("Nothing imported, invalid flows"));
+	}
+
+	let mut tx = pool.begin().await?;
+	for (name, json, visibility, token_hash) in &validated {
+		sqlx::query!(
+			r#"
+			INSERT INTO flows (name, content, visibility, token_hash) VALUES (?, ?, ?, ?)
+			ON CONFLICT (name) DO UPDATE
+			SET content = excluded.content, visibility = excluded.visibility, token_hash = excluded.token_hash
+			"#,
+			name,
+			json,
+			visibility,
+			token_hash
+		)
+		.execute(&mut *tx)
+		.await?;
+	}
+	tx.commit().await?;
+
+	Ok(validated.len())
+}
+
+#[cfg(test)]
+mod test {
+	use std::collections::BTreeMap;
+
+	use serde_json::json;
+
+	use super::{export, import, validate};
+	use crate::route::Visibility;
+
+	#[tokio::test]
+	pub async fn export_import() -> anyhow::Result<()> {
+		let pool = crate::test::pool().await?;
+
+		let feed = json!({
+			"nodes": [{ "type": "Feed", "url": "https://example.com/feed.xml", "ttl": 60 }]
+		});
+		let token_hash = "ab".repeat(32);
+		let flows: BTreeMap<_, _> = serde_json::from_value(json!({
+			"news": { "flow": feed },
+			"blog": { "flow": feed, "visibility": "private", "token_hash": token_hash },
+		}))?;
+		assert_eq!(import(&pool, flows).await?, 2);
+
+		let exported = export(&pool).await?;
+		assert_eq!(
+			exported.keys().collect::<Vec<_>>(),
+			["blog", "news"].iter().collect::<Vec<_>>()
+		);
+		assert_eq!(exported["news"].visibility, Visibility::Public);
+		assert_eq!(exported["blog"].visibility, Visibility::Private);
+		assert_eq!(exported["blog"].token_hash, Some(token_hash));
+
+		// Replaces the flows in place, so exports can be imported again.
+		let again = serde_json::from_value(serde_json::to_value(&exported)?)?;
+		assert_eq!(import(&pool, again).await?, 2);
+		assert_eq!(export(&pool).await?, exported);
+
+		// A single invalid flow aborts the import.
+		let invalid = serde_json::from_value(json!({
+			"new": { "flow": { "nodes": [{ "type": "Seen" }], "connections": [[[1, 0], [0, 0]]] } },
+			"news": exported["news"],
+		}))?;
+		let err = import(&pool, invalid).await.unwrap_err();
+		assert!(format!("{err:#}").contains("`new`"));
+		assert_eq!(export(&pool).await?.len(), 2);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	pub async fn validate_files() -> anyhow::Result<()> {
+		let dir = std::env::temp_dir().join(format!("rssflow-cli-{}", std::process::id()));
+		std::fs::create_dir_all(&dir)?;
+		std::fs::write(
+			dir.join("news.json"),
+			r#"{ "nodes": [{ "type": "Feed", "url": "https://example.com/news.xml", "ttl": 60 }] }"#,
+		)?;
+		std::fs::write(
+			dir.join("blog.toml"),
+			"[[nodes]]\ntype = \"Feed\"\nurl = \"https://example.com/blog.xml\"\nttl = 60\n",
+		)?;
+		std::fs::write(dir.join("notes.txt"), "")?;
+
+		validate(&dir.join("news.json")).await?;
+		validate(&dir.join("blog.toml")).await?;
+		assert!(validate(&dir.join("notes.txt")).await.is_err());
+
+		std::fs::remove_dir_all(&dir)?;
+		Ok(())
+	}
+}
@@ -1,4 +1,4 @@
-use std::net::IpAddr;
+use std::{net::IpAddr, path::PathBuf};
 
 use confique::Config;
 use tokio::sync::OnceCell;
@@ -17,6 +17,69 @@ pub struct AppConfig {
 
 	#[config(env = "PUBLIC_URL")]
 	pub public_url: Option<Url>,
+
+	/// Directory of flow files (`<name>.json` or `<name>.toml`), watched for changes.
+	/// Its flows replace stored flows of the same name and are read-only in the API.
+	#[config(env = "FLOWS_DIR")]
+	pub flows_dir: Option<PathBuf>,
+
+	/// Reject `WebSub` pushes that are unsigned or fail signature verification with `403`,
+	/// instead of acknowledging and dropping them as the spec asks.
+	#[config(env = "WEBSUB_STRICT", default = false)]
+	pub websub_strict: bool,
+
+	/// Keys granting full access to `/api`, on top of those stored with `POST /api/keys`.
+	/// While no key exists at all, the API rejects every request, create one with `rssflow key`.
+	#[config(
+		env = "API_KEYS",
+		parse_env = confique::env::parse::list_by_comma,
+		default = []
+	)]
+	pub api_keys: Vec<String>,
+
+	/// Keys granting read-only access to `/api`.
+	#[config(
+		env = "API_READ_KEYS",
+		parse_env = confique::env::parse::list_by_comma,
+		default = []
+	)]
+	pub api_read_keys: Vec<String>,
+
+	#[config(nested)]
+	pub http: HttpConfig,
+}
+
+/// Outbound HTTP requests, of nodes and `WebSub` alike.
+#[derive(Config)]
+pub struct HttpConfig {
+	/// Timeout of a whole request, in seconds.
+	#[config(env = "HTTP_TIMEOUT", default = 30)]
+	pub timeout: u64,
+
+	/// Timeout of connecting, in seconds.
+	#[config(env = "HTTP_CONNECT_TIMEOUT", default = 10)]
+	pub connect_timeout: u64,
+
+	/// Defaults to `rssflow/<version>`.
+	#[config(env = "HTTP_USER_AGENT")]
+	pub user_agent: Option<String>,
+
+	/// Proxy for both HTTP and HTTPS requests. Without it, the standard `HTTP_PROXY`,
+	/// `HTTPS_PROXY` and `NO_PROXY` variables apply; with it, they are ignored.
+	#[config(env = "RSSFLOW_PROXY")]
+	pub proxy: Option<Url>,
+
+	/// PEM files with CA certificates to trust, on top of the built-in ones.
+	#[config(
+		env = "HTTP_CA_CERTIFICATES",
+		parse_env = confique::env::parse::list_by_comma,
+		default = []
+	)]
+	pub ca_certificates: Vec<PathBuf>,
+
+	/// Responses larger than this many bytes are dropped.
+	#[config(env = "HTTP_MAX_RESPONSE_SIZE", default = 10_485_760)]
+	pub max_response_size: u64,
 }
 
 pub static CONFIG: OnceCell<AppConfig> = OnceCell::const_new();
@@ -0,0 +1,437 @@
+//! [JSON Feed 1.1](https://www.jsonfeed.org/version/1.1/), converted from and into Atom.
+
+use atom_syndication::{
+	Category, Content, Entry, Feed, FixedDateTime, Link, Person, Text, TextType,
+};
+use chrono::{DateTime, SecondsFormat};
+use serde::{Deserialize, Serialize};
+
+use super::Undated;
+
+pub const VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct JsonFeed {
+	pub version: String,
+	pub title: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub home_page_url: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub feed_url: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub description: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub icon: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub favicon: Option<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub authors: Vec<Author>,
+	/// JSON Feed 1.0 only allowed a single author.
+	#[serde(default, skip_serializing)]
+	pub author: Option<Author>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub language: Option<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub hubs: Vec<Hub>,
+	#[serde(default)]
+	pub items: Vec<Item>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Item {
+	pub id: Id,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub url: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub external_url: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub title: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub content_html: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub content_text: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub summary: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub image: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub date_published: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub date_modified: Option<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub authors: Vec<Author>,
+	#[serde(default, skip_serializing)]
+	pub author: Option<Author>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub tags: Vec<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub language: Option<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub attachments: Vec<Attachment>,
+}
+
+/// Item ids should be strings, but some JSON Feed 1.0 producers use numbers.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Id {
+	String(String),
+	Number(serde_json::Number),
+}
+
+impl Default for Id {
+	fn default() -> Self {
+		Self::String(String::new())
+	}
+}
+
+impl From<Id> for String {
+	fn from(id: Id) -> Self {
+		match id {
+			Id::String(id) => id,
+			Id::Number(id) => id.to_string(),
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Author {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub name: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub url: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub avatar: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Hub {
+	#[serde(rename = "type")]
+	pub kind: String,
+	pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Attachment {
+	pub url: String,
+	pub mime_type: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub title: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub size_in_bytes: Option<u64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub duration_in_seconds: Option<f64>,
+}
+
+fn link(href: String, rel: &str) -> Link {
+	Link {
+		href,
+		rel: rel.to_string(),
+		..Default::default()
+	}
+}
+
+fn find_link<'a>(links: &'a [Link], rel: &str) -> Option<&'a Link> {
+	links.iter().find(|l| l.rel.eq(rel))
+}
+
+fn date(date: Option<&str>) -> Option<FixedDateTime> {
+	DateTime::parse_from_rfc3339(date?).ok()
+}
+
+fn rfc3339(date: &FixedDateTime) -> String {
+	date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+impl From<Author> for Person {
+	fn from(author: Author) -> Self {
+		Person {
+			name: author.name.unwrap_or_default(),
+			email: None,
+			uri: author.url,
+		}
+	}
+}
+
+impl From<&Person> for Author {
+	fn from(person: &Person) -> Self {
+		Author {
+			name: Some(person.name.clone()),
+			url: person.uri.clone(),
+			avatar: None,
+		}
+	}
+}
+
+impl JsonFeed {
+	/// Converts into an Atom feed, see [`Undated`] for items without a date.
+	pub fn into_feed(self, fallback: Undated) -> Feed {
+		let mut links = Vec::new();
+		links.extend(self.home_page_url.clone().map(|u| link(u, "alternate")));
+		links.extend(self.feed_url.clone().map(|u| link(u, "self")));
+		links.extend(self.hubs.into_iter().map(|h| link(h.url, "hub")));
+
+		let entries: Vec<Entry> = self
+			.items
+			.into_iter()
+			.map(|item| item.into_entry(fallback))
+			.collect();
+		let updated = entries
+			.iter()
+			.map(|e| e.updated)
+			.max()
+			.unwrap_or_else(|| fallback.feed());
+
+		Feed {
+			id: self
+				.feed_url
+				.or(self.home_page_url)
+				.unwrap_or_else(|| self.title.clone()),
+			title: Text::plain(self.title),
+			updated,
+			authors: self
+				.authors
+				.into_iter()
+				.chain(self.author)
+				.map(Person::from)
+				.collect(),
+			icon: self.favicon,
+			logo: self.icon,
+			links,
+			subtitle: self.description.map(Text::plain),
+			lang: self.language,
+			entries,
+			..Default::default()
+		}
+	}
+}
+
+impl Item {
+	fn into_entry(self, fallback: Undated) -> Entry {
+		let mut links = Vec::new();
+		links.extend(self.url.map(|u| link(u, "alternate")));
+		links.extend(self.external_url.map(|u| link(u, "related")));
+		links.extend(self.attachments.into_iter().map(|a| Link {
+			href: a.url,
+			rel: "enclosure".to_string(),
+			mime_type: Some(a.mime_type),
+			title: a.title,
+			length: a.size_in_bytes.map(|s| s.to_string()),
+			..Default::default()
+		}));
+
+		let content = if let Some(html) = self.content_html {
+			Some(Content {
+				value: Some(html),
+				content_type: Some("html".to_string()),
+				..Default::default()
+			})
+		} else {
+			self.content_text.map(|text| Content {
+				value: Some(text),
+				content_type: Some("text".to_string()),
+				..Default::default()
+			})
+		};
+
+		let id = String::from(self.id);
+		let published = date(self.date_published.as_deref());
+		let updated = date(self.date_modified.as_deref())
+			.or(published)
+			.unwrap_or_else(|| fallback.entry(&id, None));
+
+		Entry {
+			title: Text::plain(self.title.unwrap_or_default()),
+			id,
+			updated,
+			authors: self
+				.authors
+				.into_iter()
+				.chain(self.author)
+				.map(Person::from)
+				.collect(),
+			categories: self
+				.tags
+				.into_iter()
+				.map(|term| Category {
+					term,
+					..Default::default()
+				})
+				.collect(),
+			links,
+			published,
+			summary: self.summary.map(Text::plain),
+			content,
+			..Default::default()
+		}
+	}
+}
+
+impl From<&Feed> for JsonFeed {
+	fn from(feed: &Feed) -> Self {
+		JsonFeed {
+			version: VERSION.to_string(),
+			title: feed.title.value.clone(),
+			home_page_url: find_link(&feed.links, "alternate").map(|l| l.href.clone()),
+			feed_url: find_link(&feed.links, "self").map(|l| l.href.clone()),
+			description: feed.subtitle.as_ref().map(|s| s.value.clone()),
+			icon: feed.logo.clone(),
+			favicon: feed.icon.clone(),
+			authors: feed.authors.iter().map(Author::from).collect(),
+			author: None,
+			language: feed.lang.clone(),
+			hubs: feed
+				.links
+				.iter()
+				.filter(|l| l.rel.eq("hub"))
+				.map(|l| Hub {
+					kind: "WebSub".to_string(),
+					url: l.href.clone(),
+				})
+				.collect(),
+			items: feed.entries.iter().map(Item::from).collect(),
+		}
+	}
+}
+
+impl From<&Entry> for Item {
+	fn from(entry: &Entry) -> Self {
+		let (content_html, content_text) = match &entry.content {
+			Some(Content {
+				value: Some(value),
+				content_type,
+				..
+			}) if content_type.as_deref() == Some("text") => (None, Some(value.clone())),
+			Some(Content {
+				value: Some(value), ..
+			}) => (Some(value.clone()), None),
+			// Items need one or the other, so the summary or title stands in for missing content.
+			_ => match &entry.summary {
+				Some(Text {
+					value,
+					r#type: TextType::Html | TextType::Xhtml,
+					..
+				}) => (Some(value.clone()), None),
+				Some(summary) => (None, Some(summary.value.clone())),
+				None => (None, Some(entry.title.value.clone())),
+			},
+		};
+
+		Item {
+			id: Id::String(entry.id.clone()),
+			url: find_link(&entry.links, "alternate").map(|l| l.href.clone()),
+			external_url: find_link(&entry.links, "related").map(|l| l.href.clone()),
+			title: Some(entry.title.value.clone()),
+			content_html,
+			content_text,
+			summary: entry.summary.as_ref().map(|s| s.value.clone()),
+			image: None,
+			date_published: entry.published.as_ref().map(rfc3339),
+			date_modified: Some(rfc3339(&entry.updated)),
+			authors: entry.authors.iter().map(Author::from).collect(),
+			author: None,
+			tags: entry.categories.iter().map(|c| c.term.clone()).collect(),
+			language: None,
+			attachments: entry
+				.links
+				.iter()
+				.filter(|l| l.rel.eq("enclosure"))
+				.map(|l| Attachment {
+					url: l.href.clone(),
+					mime_type: l
+						.mime_type
+						.clone()
+						.unwrap_or_else(|| "application/octet-stream".to_string()),
+					title: l.title.clone(),
+					size_in_bytes: l.length.as_ref().and_then(|l| l.parse().ok()),
+					duration_in_seconds: None,
+				})
+				.collect(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use atom_syndication::Text;
+
+	use super::{Item, JsonFeed};
+	use crate::feed::{parse, Format, Undated};
+
+	const JSON: &str = r#"{
+		"version": "https://jsonfeed.org/version/1.1",
+		"title": "Example",
+		"home_page_url": "https://example.com/",
+		"feed_url": "https://example.com/feed.json",
+		"hubs": [{ "type": "WebSub", "url": "https://hub.example.com/" }],
+		"items": [
+			{
+				"id": 1,
+				"url": "https://example.com/1",
+				"title": "First",
+				"content_html": "<p>Hello</p>",
+				"date_published": "2024-07-02T10:00:00Z",
+				"authors": [{ "name": "Jane Doe" }],
+				"tags": ["news"],
+				"attachments": [
+					{ "url": "https://example.com/1.mp3", "mime_type": "audio/mpeg", "size_in_bytes": 1234 }
+				]
+			}
+		]
+	}"#;
+
+	#[test]
+	pub fn round_trip() -> anyhow::Result<()> {
+		assert_eq!(
+			Format::sniff(JSON.as_bytes(), Some("application/atom+xml")),
+			Some(Format::Json)
+		);
+
+		let feed = parse(JSON.as_bytes(), None, Undated::default())?;
+		assert_eq!(feed.id, "https://example.com/feed.json");
+		assert!(feed.links.iter().any(|l| l.rel == "hub"));
+
+		let entry = &feed.entries[0];
+		assert_eq!(entry.id, "1");
+		assert_eq!(entry.authors[0].name, "Jane Doe");
+		assert_eq!(entry.updated, entry.published.unwrap());
+
+		// Items without dates get one that doesn't change between parses.
+		let undated = r#"{ "version": "https://jsonfeed.org/version/1.1", "title": "Undated", "items": [{ "id": 1 }] }"#;
+		let previous = parse(undated.as_bytes(), None, Undated::default())?;
+		let again = parse(
+			undated.as_bytes(),
+			None,
+			Undated {
+				previous: Some(&previous),
+				modified: None,
+			},
+		)?;
+		assert_eq!(again.entries[0].updated, previous.entries[0].updated);
+
+		let json = JsonFeed::from(&feed);
+		let item = &json.items[0];
+		assert_eq!(
+			json.feed_url.as_deref(),
+			Some("https://example.com/feed.json")
+		);
+		assert_eq!(json.hubs[0].url, "https://hub.example.com/");
+		assert_eq!(item.content_html.as_deref(), Some("<p>Hello</p>"));
+		assert_eq!(item.tags, ["news"]);
+		assert_eq!(item.attachments[0].size_in_bytes, Some(1234));
+		assert_eq!(item.date_published.as_deref(), Some("2024-07-02T10:00:00Z"));
+
+		// Entries without content fall back to their summary, then their title.
+		let mut entry = feed.entries[0].clone();
+		entry.content = None;
+		entry.summary = Some(Text::plain("Summary"));
+		let item = Item::from(&entry);
+		assert_eq!(
+			(item.content_html.as_deref(), item.content_text.as_deref()),
+			(None, Some("Summary"))
+		);
+		entry.summary = None;
+		assert_eq!(Item::from(&entry).content_text.as_deref(), Some("First"));
+
+		Ok(())
+	}
+}
@@ -1,113 +1,656 @@
-// use anyhow::anyhow;
-// use atom_syndication::{
-// 	CategoryBuilder, Entry, EntryBuilder, FeedBuilder, FixedDateTime, LinkBuilder, PersonBuilder,
-// 	Text,
-// };
-// use rss::Channel;
-// use serde::{Deserialize, Serialize};
-// use std::ops::{Deref, DerefMut};
-//
-// #[derive(Serialize, Deserialize)]
-// // pub struct Feed {
-// // 	pub title: Text,
-// // 	pub id: String,
-// // }
-// pub struct Feed(atom_syndication::Feed);
-//
-// impl TryFrom<Channel> for Feed {
-// 	type Error = anyhow::Error;
-//
-// 	fn try_from(value: Channel) -> Result<Self, Self::Error> {
-// 		let mut builder = FeedBuilder::default();
-// 		builder
-// 			.title(value.title)
-// 			.link(LinkBuilder::default().href(value.link).build())
-// 			.subtitle(Text::plain(value.description));
-//
-// 		if let Some(language) = value.language {
-// 			builder.lang(Some(language));
-// 		}
-//
-// 		if let Some(atom) = value.atom_ext {
-// 			for link in atom.links {
-// 				builder.link(link);
-// 			}
-// 		}
-//
-// 		if let Some(last_build_date) = value.last_build_date {
-// 			builder.updated(
-// 				FixedDateTime::parse_from_rfc2822(&last_build_date).map_err(|e| anyhow!(e))?,
-// 			);
-// 		}
-//
-// 		for item in value.items {
-// 			let mut entry = EntryBuilder::default();
-//
-// 			if let Some(title) = item.title {
-// 				entry.title(title);
-// 			}
-//
-// 			if let Some(description) = item.description {
-// 				entry.summary(Text::plain(description));
-// 			}
-//
-// 			if let Some(link) = item.link {
-// 				entry.link(LinkBuilder::default().href(link).build());
-// 			}
-//
-// 			if let Some(pub_date) = item.pub_date {
-// 				let published =
-// 					FixedDateTime::parse_from_rfc2822(&pub_date).map_err(|e| anyhow!(e))?;
-// 				entry.published(published);
-// 			}
-//
-// 			for category in &value.categories {
-// 				entry.category(
-// 					CategoryBuilder::default()
-// 						.label(category.name.clone())
-// 						.build(),
-// 				);
-// 			}
-//
-// 			entry.i
-//
-// 			if let Some(dc) = item.dublin_core_ext {
-// 				for creator in dc.creators {
-// 					entry.author(PersonBuilder::default().name(creator).build());
-// 				}
-// 			}
-// 		}
-//
-// 		Ok(Feed(builder.build()))
-// 	}
-// }
-//
-// impl From<atom_syndication::Feed> for Feed {
-// 	fn from(value: atom_syndication::Feed) -> Self {
-// 		// Feed { title: value.title }
-// 		Feed(value)
-// 	}
-// }
-//
-// impl Deref for Feed {
-// 	type Target = atom_syndication::Feed;
-//
-// 	fn deref(&self) -> &Self::Target {
-// 		&self.0
-// 	}
-// }
-//
-// impl DerefMut for Feed {
-// 	fn deref_mut(&mut self) -> &mut Self::Target {
-// 		&mut self.0
-// 	}
-// }
-//
-// //
-// // impl From<Feed> for atom_syndication::Feed {
-// // 	fn from(value: Feed) -> Self {
-// // 		atom_syndication::FeedBuilder::default()
-// // 			.title(value.title)
-// // 			.build()
-// // 	}
-// // }
+use anyhow::anyhow;
+use atom_syndication::{
+	Category, Content, Entry, Feed, FixedDateTime, Generator, Link, Person, Text, TextType,
+};
+use chrono::{DateTime, Utc};
+use rss::{
+	extension::{atom::AtomExtension, dublincore::DublinCoreExtension},
+	Channel, Enclosure, Guid, Image, Item,
+};
+
+pub mod json;
+
+/// Syndication formats accepted by the `Feed` node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+	Atom,
+	/// RSS 0.9x, 1.0 (RDF) and 2.0
+	Rss,
+	/// JSON Feed 1.0 and 1.1
+	Json,
+}
+
+impl Format {
+	/// Detects the format of `content` from its root element, falling back to the `Content-Type` header.
+	pub fn sniff(content: &[u8], content_type: Option<&str>) -> Option<Self> {
+		if content
+			.iter()
+			.find(|b| !b.is_ascii_whitespace())
+			.is_some_and(|b| *b == b'{')
+		{
+			return Some(Self::Json);
+		}
+
+		match root_element(content).as_deref() {
+			Some("feed") => return Some(Self::Atom),
+			Some("rss" | "rdf:RDF" | "RDF") => return Some(Self::Rss),
+			_ => {}
+		}
+
+		match mime(content_type)? {
+			"application/atom+xml" => Some(Self::Atom),
+			"application/rss+xml" | "application/rdf+xml" => Some(Self::Rss),
+			"application/feed+json" | "application/json" => Some(Self::Json),
+			_ => None,
+		}
+	}
+}
+
+fn mime(content_type: Option<&str>) -> Option<&str> {
+	Some(content_type?.split(';').next()?.trim())
+}
+
+/// Stands in for dates missing from a feed, so that they stay the same from one parse to the next.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Undated<'a> {
+	/// The feed as parsed last time, whose entries keep their dates.
+	pub previous: Option<&'a Feed>,
+	/// When the document last changed, from its `Last-Modified` header.
+	pub modified: Option<FixedDateTime>,
+}
+
+impl Undated<'_> {
+	/// Date of an entry without one: its date last time, else the feed's, else when the document
+	/// last changed. Only entries seen for the first time in a document without dates get the
+	/// current time.
+	pub fn entry(&self, id: &str, feed: Option<FixedDateTime>) -> FixedDateTime {
+		self.previous
+			.and_then(|previous| previous.entries.iter().find(|e| e.id == id))
+			.map(|e| e.updated)
+			.or(feed)
+			.or(self.modified)
+			.unwrap_or_else(|| Utc::now().fixed_offset())
+	}
+
+	/// Date of a feed without one nor entries.
+	pub fn feed(&self) -> FixedDateTime {
+		self.modified
+			.or(self.previous.map(|previous| previous.updated))
+			.unwrap_or_else(|| Utc::now().fixed_offset())
+	}
+}
+
+/// Parses an Atom, RSS or JSON Feed document, upgrading RSS and JSON Feed to Atom.
+///
+/// Generic XML documents of an unrecognised format are tried as Atom, then as RSS.
+pub fn parse(
+	content: &[u8],
+	content_type: Option<&str>,
+	fallback: Undated,
+) -> anyhow::Result<Feed> {
+	match Format::sniff(content, content_type) {
+		Some(Format::Atom) => Ok(Feed::read_from(content)?),
+		Some(Format::Rss) => Ok(from_rss(Channel::read_from(content)?, fallback)),
+		Some(Format::Json) => {
+			Ok(serde_json::from_slice::<json::JsonFeed>(content)?.into_feed(fallback))
+		}
+		None if matches!(mime(content_type), Some("text/xml" | "application/xml")) => {
+			Feed::read_from(content).or_else(|atom| {
+				Channel::read_from(content)
+					.map(|channel| from_rss(channel, fallback))
+					.map_err(|rss| anyhow!("Neither Atom ({atom}) nor RSS ({rss})"))
+			})
+		}
+		None => Err(anyhow!(
+			"Unrecognised feed format ({})",
+			content_type.unwrap_or("no Content-Type")
+		)),
+	}
+}
+
+/// How much of a document is searched for its root element.
+const SNIFF_LEN: usize = 4096;
+
+/// Name of the first element in an XML document, skipping the prolog, comments and doctype.
+///
+/// The start of the document is decoded lossily, as the prolog and root element are ASCII in the
+/// encodings feeds use besides UTF-8, like ISO-8859-1 and Windows-1252.
+fn root_element(content: &[u8]) -> Option<String> {
+	let prefix = String::from_utf8_lossy(&content[..content.len().min(SNIFF_LEN)]);
+	let mut rest = prefix.trim_start_matches('\u{feff}');
+
+	loop {
+		rest = rest.trim_start();
+		if let Some(r) = rest.strip_prefix("<?") {
+			rest = &r[r.find("?>")? + 2..];
+		} else if let Some(r) = rest.strip_prefix("<!--") {
+			rest = &r[r.find("-->")? + 3..];
+		} else if let Some(r) = rest.strip_prefix("<!") {
+			rest = &r[r.find('>')? + 1..];
+		} else {
+			let r = rest.strip_prefix('<')?;
+			let end = r.find(|c: char| c.is_whitespace() || c == '>' || c == '/')?;
+			return Some(r[..end].to_string());
+		}
+	}
+}
+
+/// RSS dates are RFC 2822, Dublin Core (`dc:date`) ones RFC 3339.
+pub(crate) fn parse_date(date: &str) -> Option<FixedDateTime> {
+	DateTime::parse_from_rfc2822(date.trim())
+		.or_else(|_| DateTime::parse_from_rfc3339(date.trim()))
+		.ok()
+}
+
+fn html(value: String) -> Text {
+	Text {
+		value,
+		r#type: TextType::Html,
+		..Default::default()
+	}
+}
+
+fn category(category: rss::Category) -> Category {
+	Category {
+		term: category.name,
+		scheme: category.domain,
+		label: None,
+	}
+}
+
+/// `author` elements hold an email address, optionally followed by a name in parentheses.
+fn person(author: &str) -> Person {
+	if let Some((email, name)) = author.split_once('(') {
+		Person {
+			name: name.trim_end_matches(')').trim().to_string(),
+			email: Some(email.trim().to_string()),
+			uri: None,
+		}
+	} else if author.contains('@') {
+		Person {
+			name: author.to_string(),
+			email: Some(author.to_string()),
+			uri: None,
+		}
+	} else {
+		Person {
+			name: author.to_string(),
+			..Default::default()
+		}
+	}
+}
+
+/// Converts an RSS channel into an Atom feed.
+///
+/// | RSS                           | Atom                                  |
+/// |-------------------------------|---------------------------------------|
+/// | `guid`, else `link`           | `id`                                  |
+/// | `link`                        | `link rel="alternate"`                |
+/// | `enclosure`                   | `link rel="enclosure"`                |
+/// | `description`                 | `summary` (html)                      |
+/// | `content:encoded`             | `content` (html)                      |
+/// | `author`, `dc:creator`        | `author`                              |
+/// | `category`                    | `category` (`domain` as `scheme`)     |
+/// | `pubDate`, else `dc:date`     | `published` and `updated`             |
+/// | `atom:link` (`self`, `hub`)   | `link`                                |
+///
+/// Items without a date get the channel's, see [`Undated`] for channels without one either.
+pub fn from_rss(channel: Channel, fallback: Undated) -> Feed {
+	let updated = channel
+		.last_build_date
+		.as_deref()
+		.or(channel.pub_date.as_deref())
+		.and_then(parse_date);
+
+	let mut links = vec![Link {
+		href: channel.link.clone(),
+		rel: "alternate".to_string(),
+		..Default::default()
+	}];
+	if let Some(atom) = channel.atom_ext {
+		links.extend(atom.links);
+	}
+
+	let mut authors: Vec<Person> = channel
+		.managing_editor
+		.as_deref()
+		.map(person)
+		.into_iter()
+		.collect();
+	if let Some(dc) = &channel.dublin_core_ext {
+		authors.extend(dc.creators.iter().map(|c| person(c)));
+	}
+
+	let entries: Vec<Entry> = channel
+		.items
+		.into_iter()
+		.map(|item| entry(item, updated, fallback))
+		.collect();
+
+	let updated = updated
+		.or_else(|| entries.iter().map(|e| e.updated).max())
+		.unwrap_or_else(|| fallback.feed());
+
+	Feed {
+		title: Text::plain(channel.title),
+		id: links
+			.iter()
+			.find(|l| l.rel.eq("self"))
+			.map_or(channel.link, |l| l.href.clone()),
+		updated,
+		authors,
+		categories: channel.categories.into_iter().map(category).collect(),
+		generator: channel.generator.map(|value| Generator {
+			value,
+			..Default::default()
+		}),
+		logo: channel.image.map(|i| i.url),
+		links,
+		rights: channel.copyright.map(Text::plain),
+		subtitle: Some(channel.description)
+			.filter(|d| !d.is_empty())
+			.map(Text::plain),
+		lang: channel.language,
+		entries,
+		..Default::default()
+	}
+}
+
+fn entry(item: Item, channel: Option<FixedDateTime>, fallback: Undated) -> Entry {
+	let published = item.pub_date.as_deref().and_then(parse_date).or_else(|| {
+		item.dublin_core_ext
+			.as_ref()
+			.and_then(|dc| dc.dates.first())
+			.and_then(|d| parse_date(d))
+	});
+
+	let mut links = Vec::new();
+	if let Some(link) = &item.link {
+		links.push(Link {
+			href: link.clone(),
+			rel: "alternate".to_string(),
+			..Default::default()
+		});
+	}
+	if let Some(enclosure) = item.enclosure {
+		links.push(Link {
+			href: enclosure.url,
+			rel: "enclosure".to_string(),
+			mime_type: Some(enclosure.mime_type).filter(|m| !m.is_empty()),
+			length: Some(enclosure.length).filter(|l| !l.is_empty()),
+			..Default::default()
+		});
+	}
+	if let Some(atom) = item.atom_ext {
+		links.extend(atom.links);
+	}
+
+	let mut authors: Vec<Person> = item.author.as_deref().map(person).into_iter().collect();
+	if let Some(dc) = &item.dublin_core_ext {
+		authors.extend(dc.creators.iter().map(|c| person(c)));
+	}
+
+	let id = item
+		.guid
+		.map(|g| g.value)
+		.or_else(|| item.link.clone())
+		.or_else(|| item.title.clone())
+		.unwrap_or_default();
+	let updated = published.unwrap_or_else(|| fallback.entry(&id, channel));
+
+	Entry {
+		title: Text::plain(item.title.unwrap_or_default()),
+		id,
+		updated,
+		authors,
+		categories: item.categories.into_iter().map(category).collect(),
+		links,
+		published,
+		summary: item.description.map(html),
+		content: item.content.map(|value| Content {
+			value: Some(value),
+			content_type: Some("html".to_string()),
+			..Default::default()
+		}),
+		..Default::default()
+	}
+}
+
+fn rss_category(category: &Category) -> rss::Category {
+	rss::Category {
+		name: category.term.clone(),
+		domain: category.scheme.clone(),
+	}
+}
+
+fn alternate(links: &[Link]) -> Option<&Link> {
+	links
+		.iter()
+		.find(|l| l.rel.eq("alternate") || l.rel.is_empty())
+}
+
+/// Converts an Atom feed into an RSS 2.0 channel.
+///
+/// | Atom                              | RSS                                               |
+/// |-----------------------------------|---------------------------------------------------|
+/// | `id`                              | `guid` (`isPermaLink` when equal to `link`)       |
+/// | `link rel="alternate"`, else `id` | `link`                                            |
+/// | `link rel="enclosure"`            | `enclosure` (the first one, RSS allows only one)  |
+/// | `link rel="self"`, `rel="hub"`    | `atom:link`                                       |
+/// | `summary`, else `content`         | `description`                                     |
+/// | `content`                         | `content:encoded`                                 |
+/// | `author` with an email            | `author` (`email (name)`)                         |
+/// | `author` without an email         | `dc:creator`                                      |
+/// | `category`                        | `category` (`scheme` as `domain`)                 |
+/// | `published`, else `updated`       | `pubDate`                                         |
+/// | feed `updated`                    | `lastBuildDate`                                   |
+/// | feed `subtitle`                   | `description`                                     |
+/// | feed `logo`                       | `image`                                           |
+/// | feed `rights`                     | `copyright`                                       |
+pub fn to_rss(feed: &Feed) -> Channel {
+	let link = alternate(&feed.links).map_or_else(|| feed.id.clone(), |l| l.href.clone());
+
+	Channel {
+		title: feed.title.value.clone(),
+		link: link.clone(),
+		description: feed
+			.subtitle
+			.as_ref()
+			.map(|s| s.value.clone())
+			.unwrap_or_default(),
+		language: feed.lang.clone(),
+		copyright: feed.rights.as_ref().map(|r| r.value.clone()),
+		last_build_date: Some(feed.updated.to_rfc2822()),
+		categories: feed.categories.iter().map(rss_category).collect(),
+		generator: feed.generator.as_ref().map(|g| g.value.clone()),
+		image: feed.logo.as_ref().map(|url| Image {
+			url: url.clone(),
+			title: feed.title.value.clone(),
+			link,
+			..Default::default()
+		}),
+		atom_ext: atom_links(&feed.links),
+		dublin_core_ext: creators(&feed.authors),
+		items: feed.entries.iter().map(item).collect(),
+		..Default::default()
+	}
+}
+
+fn atom_links(links: &[Link]) -> Option<AtomExtension> {
+	let links: Vec<Link> = links
+		.iter()
+		.filter(|l| l.rel.eq("self") || l.rel.eq("hub"))
+		.cloned()
+		.collect();
+
+	(!links.is_empty()).then_some(AtomExtension { links })
+}
+
+fn creators(authors: &[Person]) -> Option<DublinCoreExtension> {
+	let creators: Vec<String> = authors
+		.iter()
+		.filter(|a| a.email.is_none())
+		.map(|a| a.name.clone())
+		.collect();
+
+	(!creators.is_empty()).then(|| DublinCoreExtension {
+		creators,
+		..Default::default()
+	})
+}
+
+fn item(entry: &Entry) -> Item {
+	let link = alternate(&entry.links).map(|l| l.href.clone());
+	let content = entry.content.as_ref().and_then(|c| c.value.clone());
+
+	Item {
+		title: Some(entry.title.value.clone()),
+		guid: Some(Guid {
+			permalink: link.as_ref().is_some_and(|l| l.eq(&entry.id)),
+			value: entry.id.clone(),
+		}),
+		link,
+		description: entry
+			.summary
+			.as_ref()
+			.map(|s| s.value.clone())
+			.or_else(|| content.clone()),
+		content,
+		author: entry
+			.authors
+			.iter()
+			.find_map(|a| Some(format!("{} ({})", a.email.as_ref()?, a.name))),
+		categories: entry.categories.iter().map(rss_category).collect(),
+		enclosure: entry
+			.links
+			.iter()
+			.find(|l| l.rel.eq("enclosure"))
+			.map(|l| Enclosure {
+				url: l.href.clone(),
+				length: l.length.clone().unwrap_or_else(|| "0".to_string()),
+				mime_type: l
+					.mime_type
+					.clone()
+					.unwrap_or_else(|| "application/octet-stream".to_string()),
+			}),
+		pub_date: Some(entry.published.unwrap_or(entry.updated).to_rfc2822()),
+		atom_ext: atom_links(&entry.links),
+		dublin_core_ext: creators(&entry.authors),
+		..Default::default()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{parse, to_rss, Format, Undated};
+
+	const RSS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- generated -->
+<rss version="2.0"
+	xmlns:atom="http://www.w3.org/2005/Atom"
+	xmlns:content="http://purl.org/rss/1.0/modules/content/"
+	xmlns:dc="http://purl.org/dc/elements/1.1/">
+<channel>
+	<title>Example</title>
+	<link>https://example.com/</link>
+	<description>An example feed</description>
+	<atom:link href="https://example.com/feed" rel="self" type="application/rss+xml"/>
+	<atom:link href="https://hub.example.com/" rel="hub"/>
+	<item>
+		<title>First</title>
+		<link>https://example.com/1</link>
+		<guid isPermaLink="false">tag:example.com,2024:1</guid>
+		<description>&lt;p&gt;Summary&lt;/p&gt;</description>
+		<content:encoded><![CDATA[<p>Full content</p>]]></content:encoded>
+		<dc:creator>Jane Doe</dc:creator>
+		<category domain="https://example.com/tags">news</category>
+		<enclosure url="https://example.com/1.mp3" length="1234" type="audio/mpeg"/>
+		<pubDate>Tue, 02 Jul 2024 10:00:00 +0000</pubDate>
+	</item>
+</channel>
+</rss>"#;
+
+	const RDF: &str = r#"<?xml version="1.0"?>
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#" xmlns="http://purl.org/rss/1.0/">
+	<channel rdf:about="https://example.com/">
+		<title>Example</title>
+		<link>https://example.com/</link>
+		<description>An example feed</description>
+	</channel>
+	<item rdf:about="https://example.com/1">
+		<title>First</title>
+		<link>https://example.com/1</link>
+	</item>
+</rdf:RDF>"#;
+
+	#[test]
+	pub fn sniff() {
+		// The document wins over a wrong header.
+		assert_eq!(
+			Format::sniff(RSS.as_bytes(), Some("application/atom+xml")),
+			Some(Format::Rss)
+		);
+		assert_eq!(Format::sniff(RDF.as_bytes(), None), Some(Format::Rss));
+		assert_eq!(
+			Format::sniff(b"<feed xmlns=\"http://www.w3.org/2005/Atom\"/>", None),
+			Some(Format::Atom)
+		);
+		assert_eq!(
+			Format::sniff(b"garbage", Some("application/rss+xml; charset=utf-8")),
+			Some(Format::Rss)
+		);
+		assert_eq!(Format::sniff(b"<html></html>", Some("text/html")), None);
+
+		// `é` in ISO-8859-1 isn't valid UTF-8.
+		let latin1 = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<!-- caf\xe9 -->\n<rss version=\"2.0\"><channel><title>Caf\xe9</title></channel></rss>";
+		assert_eq!(Format::sniff(latin1, None), Some(Format::Rss));
+		assert_eq!(
+			parse(latin1, None, Undated::default()).unwrap().title.value,
+			"Caf\u{e9}"
+		);
+	}
+
+	#[test]
+	pub fn generic_xml() -> anyhow::Result<()> {
+		// A long header comment pushes the root element past what is sniffed.
+		let comment = format!("<!-- {} -->\n", "license ".repeat(1000));
+		let atom = format!(
+			r#"{comment}<feed xmlns="http://www.w3.org/2005/Atom"><title>Atom</title></feed>"#
+		);
+		assert_eq!(Format::sniff(atom.as_bytes(), Some("text/xml")), None);
+		let feed = parse(
+			atom.as_bytes(),
+			Some("text/xml; charset=utf-8"),
+			Undated::default(),
+		)?;
+		assert_eq!(feed.title.value, "Atom");
+
+		let rss =
+			format!(r#"{comment}<rss version="2.0"><channel><title>RSS</title></channel></rss>"#);
+		assert_eq!(
+			parse(rss.as_bytes(), Some("application/xml"), Undated::default())?
+				.title
+				.value,
+			"RSS"
+		);
+		assert!(parse(rss.as_bytes(), Some("text/html"), Undated::default()).is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	pub fn undated() -> anyhow::Result<()> {
+		let rss = |items: &str| {
+			format!(r#"<rss version="2.0"><channel><title>Undated</title>{items}</channel></rss>"#)
+		};
+		let first = rss("<item><guid>1</guid></item>");
+		let modified = chrono::DateTime::parse_from_rfc2822("Tue, 02 Jul 2024 10:00:00 GMT")?;
+
+		let feed = parse(
+			first.as_bytes(),
+			None,
+			Undated {
+				previous: None,
+				modified: Some(modified),
+			},
+		)?;
+		assert_eq!(feed.entries[0].updated, modified);
+		assert_eq!(feed.updated, modified);
+
+		// Entries seen before keep their date, new ones get the current time.
+		let second = rss("<item><guid>2</guid></item><item><guid>1</guid></item>");
+		let again = parse(
+			second.as_bytes(),
+			None,
+			Undated {
+				previous: Some(&feed),
+				modified: None,
+			},
+		)?;
+		assert_eq!(again.entries[1].updated, modified);
+		assert!(again.entries[0].updated > modified);
+
+		Ok(())
+	}
+
+	#[test]
+	pub fn rss() -> anyhow::Result<()> {
+		let feed = parse(
+			RSS.as_bytes(),
+			Some("application/rss+xml"),
+			Undated::default(),
+		)?;
+
+		assert_eq!(feed.id, "https://example.com/feed");
+		assert!(feed.links.iter().any(|l| l.rel == "hub"));
+
+		let entry = &feed.entries[0];
+		assert_eq!(entry.id, "tag:example.com,2024:1");
+		assert_eq!(entry.authors[0].name, "Jane Doe");
+		assert_eq!(entry.categories[0].term, "news");
+		assert_eq!(
+			entry.categories[0].scheme.as_deref(),
+			Some("https://example.com/tags")
+		);
+		assert_eq!(
+			entry.content.as_ref().and_then(|c| c.value.as_deref()),
+			Some("<p>Full content</p>")
+		);
+		assert_eq!(
+			entry.summary.as_ref().map(|s| s.value.as_str()),
+			Some("<p>Summary</p>")
+		);
+
+		let enclosure = entry.links.iter().find(|l| l.rel == "enclosure").unwrap();
+		assert_eq!(enclosure.href, "https://example.com/1.mp3");
+		assert_eq!(enclosure.mime_type.as_deref(), Some("audio/mpeg"));
+		assert_eq!(enclosure.length.as_deref(), Some("1234"));
+
+		// The upgraded feed must be valid Atom.
+		atom_syndication::Feed::read_from(feed.to_string().as_bytes())?;
+
+		Ok(())
+	}
+
+	#[test]
+	pub fn rdf() -> anyhow::Result<()> {
+		let feed = parse(RDF.as_bytes(), None, Undated::default())?;
+
+		assert_eq!(feed.entries.len(), 1);
+		assert_eq!(feed.entries[0].id, "https://example.com/1");
+
+		Ok(())
+	}
+
+	#[test]
+	pub fn to_rss_round_trip() -> anyhow::Result<()> {
+		let channel = to_rss(&parse(RSS.as_bytes(), None, Undated::default())?);
+		let xml = channel.to_string();
+		let channel = rss::Channel::read_from(xml.as_bytes())?;
+
+		assert_eq!(channel.link, "https://example.com/");
+		assert_eq!(
+			channel.atom_ext.map(|a| a.links.len()),
+			Some(2),
+			"self and hub links are kept"
+		);
+
+		let item = &channel.items[0];
+		assert_eq!(
+			item.guid.as_ref().map(|g| (g.value.as_str(), g.permalink)),
+			Some(("tag:example.com,2024:1", false))
+		);
+		assert_eq!(item.content.as_deref(), Some("<p>Full content</p>"));
+		assert_eq!(
+			item.pub_date.as_deref(),
+			Some("Tue, 2 Jul 2024 10:00:00 +0000")
+		);
+
+		let enclosure = item.enclosure.as_ref().unwrap();
+		assert_eq!(enclosure.url, "https://example.com/1.mp3");
+		assert_eq!(enclosure.length, "1234");
+		assert_eq!(enclosure.mime_type, "audio/mpeg");
+
+		Ok(())
+	}
+}
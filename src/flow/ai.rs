@@ -3,13 +3,18 @@ use std::{cmp::min, slice, sync::Arc};
 use anyhow::anyhow;
 use async_trait::async_trait;
 use futures::stream::{self, StreamExt};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
-use super::node::{Data, DataKind, NodeTrait, IO};
+use super::{
+	node::{Data, DataKind, NodeTrait, IO},
+	Context,
+};
+use crate::http::HttpClient;
 
 /// Generates a response using an AI assistant.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct AI {
 	url: Url, // Ollama endpoint URL.
 
@@ -17,24 +22,14 @@ pub struct AI {
 	// prompt: String,
 	system: String,
 
+	#[serde(skip)]
+	http: HttpClient,
 	#[serde(skip)]
 	input: Arc<IO>,
 	#[serde(skip)]
 	output: Arc<IO>,
 }
 
-impl AI {
-	pub fn new(url: Url, model: String, system: String) -> Self {
-		Self {
-			url,
-			model,
-			system,
-			input: Arc::default(),
-			output: Arc::default(),
-		}
-	}
-}
-
 #[async_trait]
 impl NodeTrait for AI {
 	fn inputs(&self) -> &[Arc<IO>] {
@@ -65,20 +60,16 @@ impl NodeTrait for AI {
 				.map(|mut item| async move {
 					let mut content = item.content.unwrap();
 
-					let resp = reqwest::Client::new()
-						.post(self.url.clone())
-						.json(&OllamaRequest {
-							model: self.model.clone(),
-							prompt: content.value.unwrap(),
-							stream: Some(false),
-							system: Some(self.system.clone()),
-							..Default::default()
-						})
-						.send()
-						.await?;
-
-					resp.error_for_status_ref()?;
-					let body: OllamaResponse = resp.json().await?;
+					let request = self.http.post(self.url.clone()).json(&OllamaRequest {
+						model: self.model.clone(),
+						prompt: content.value.unwrap(),
+						stream: Some(false),
+						system: Some(self.system.clone()),
+						..Default::default()
+					});
+					let resp = self.http.send(request).await?;
+
+					let body: OllamaResponse = self.http.json(resp.error_for_status()?).await?;
 
 					content.value = Some(body.response);
 					item.content = Some(content);
@@ -98,6 +89,10 @@ impl NodeTrait for AI {
 	fn set_output(&mut self, _index: usize, output: Arc<IO>) {
 		self.output = output;
 	}
+
+	fn init(&mut self, _index: usize, context: &Context) {
+		self.http = context.http.clone();
+	}
 }
 
 #[derive(Serialize, Default)]
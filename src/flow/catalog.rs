@@ -0,0 +1,156 @@
+//! Node types compiled into this build, and JSON Schemas to write flows by hand.
+
+use schemars::{gen::SchemaGenerator, schema::RootSchema, JsonSchema};
+use serde::Serialize;
+
+#[cfg(feature = "filter")]
+use super::filter::Filter;
+#[cfg(feature = "html")]
+use super::html::Html;
+#[cfg(feature = "retrieve")]
+use super::retrieve::Retrieve;
+#[cfg(feature = "sanitise")]
+use super::sanitise::Sanitise;
+use super::{ai::AI, feed::Feed, merge::Merge, node::DataKind, seen::Seen, FlowBuilder};
+
+#[derive(Serialize)]
+pub struct NodeType {
+	/// Value of the node's `type` field.
+	r#type: &'static str,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	description: Option<String>,
+	inputs: &'static [DataKind],
+	outputs: &'static [DataKind],
+	/// Whether the inputs repeat, once per feed to merge for `Merge`.
+	#[serde(skip_serializing_if = "std::ops::Not::not")]
+	variadic: bool,
+	/// Config of the node, the fields next to `type`.
+	schema: RootSchema,
+}
+
+fn node<T: JsonSchema>(
+	r#type: &'static str,
+	inputs: &'static [DataKind],
+	outputs: &'static [DataKind],
+) -> NodeType {
+	let schema = SchemaGenerator::default().into_root_schema_for::<T>();
+
+	NodeType {
+		r#type,
+		description: schema
+			.schema
+			.metadata
+			.as_ref()
+			.and_then(|m| m.description.clone()),
+		inputs,
+		outputs,
+		variadic: false,
+		schema,
+	}
+}
+
+/// Every node type that can be used in a flow file.
+pub fn nodes() -> Vec<NodeType> {
+	const FEED: &[DataKind] = &[DataKind::Feed];
+	const WEB_SUB: &[DataKind] = &[DataKind::WebSub];
+
+	vec![
+		node::<AI>("AI", FEED, FEED),
+		node::<Feed>("Feed", WEB_SUB, FEED),
+		#[cfg(feature = "filter")]
+		node::<Filter>("Filter", FEED, FEED),
+		#[cfg(feature = "html")]
+		node::<Html>("Html", WEB_SUB, FEED),
+		NodeType {
+			variadic: true,
+			..node::<Merge>("Merge", FEED, FEED)
+		},
+		#[cfg(feature = "retrieve")]
+		node::<Retrieve>("Retrieve", FEED, FEED),
+		#[cfg(feature = "sanitise")]
+		node::<Sanitise>("Sanitise", FEED, FEED),
+		node::<Seen>("Seen", FEED, FEED),
+	]
+}
+
+/// Schema of a flow file.
+pub fn flow_schema() -> RootSchema {
+	SchemaGenerator::default().into_root_schema_for::<FlowBuilder>()
+}
+
+#[cfg(test)]
+mod test {
+	use std::collections::BTreeSet;
+
+	use schemars::gen::SchemaGenerator;
+	use serde_json::json;
+
+	use super::{flow_schema, nodes};
+	use crate::flow::{
+		node::{Node, NodeTrait},
+		Context,
+	};
+
+	#[test]
+	pub fn catalog() {
+		// Every variant of `Node` that can be deserialized is listed.
+		let schema =
+			serde_json::to_value(SchemaGenerator::default().into_root_schema_for::<Node>())
+				.unwrap();
+		let tagged: BTreeSet<_> = schema["oneOf"]
+			.as_array()
+			.unwrap()
+			.iter()
+			.map(|variant| {
+				variant["properties"]["type"]["enum"][0]
+					.as_str()
+					.unwrap()
+					.to_string()
+			})
+			.collect();
+		let listed: BTreeSet<_> = nodes().iter().map(|n| n.r#type.to_string()).collect();
+		assert_eq!(tagged, listed);
+
+		let feed = nodes().into_iter().find(|n| n.r#type == "Feed").unwrap();
+		assert!(feed.description.unwrap().starts_with("HTTP GET"));
+		let properties = &feed.schema.schema.object.unwrap().properties;
+		assert!(properties.contains_key("url") && properties.contains_key("ttl"));
+		assert!(!properties.contains_key("last_fetch"));
+
+		let flow = serde_json::to_value(flow_schema()).unwrap();
+		assert!(flow["definitions"]["Node"].is_object());
+	}
+
+	/// The kinds listed are the ones the nodes declare.
+	#[test]
+	pub fn kinds() {
+		let configs = json!({
+			"AI": { "url": "http://localhost:11434/", "model": "llama3", "system": "" },
+			"Feed": { "url": "https://example.com/feed.xml", "ttl": 60 },
+			"Filter": { "field": "Title", "filter": { "contains": "rust" }, "invert": false },
+			"Html": { "url": "https://example.com/", "ttl": 60, "entry": "article", "title": "h2" },
+			"Merge": { "count": 2 },
+			"Retrieve": { "content": "main" },
+			"Sanitise": { "field": "Content" },
+			"Seen": {},
+		});
+
+		for listed in nodes() {
+			let mut config = configs[listed.r#type].clone();
+			config["type"] = json!(listed.r#type);
+			let mut node: Node = serde_json::from_value(config)
+				.unwrap_or_else(|err| panic!("`{}` config: {err}", listed.r#type));
+			node.init(0, &Context::default());
+
+			if listed.variadic {
+				assert!(node
+					.input_types()
+					.chunks(listed.inputs.len())
+					.all(|chunk| chunk == listed.inputs));
+			} else {
+				assert_eq!(node.input_types(), listed.inputs, "`{}`", listed.r#type);
+			}
+			assert_eq!(node.output_types(), listed.outputs, "`{}`", listed.r#type);
+		}
+	}
+}
@@ -7,32 +7,68 @@ use std::{
 
 use anyhow::anyhow;
 use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use parking_lot::Mutex;
-use reqwest::{header, header::LINK};
+use reqwest::{
+	header::{
+		self, HeaderMap, CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+		LINK, RETRY_AFTER,
+	},
+	StatusCode,
+};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DurationSeconds};
+use sqlx::SqlitePool;
 use url::Url;
 
-use super::node::{Data, DataKind, NodeTrait, IO};
-use crate::subscriber::websub::WebSub;
+use super::{
+	node::{Data, DataKind, NodeTrait, IO},
+	Context,
+};
+use crate::{
+	feed::{self, Undated},
+	http::HttpClient,
+	subscriber::websub::WebSub,
+};
+
+/// Upper bound for how long `Cache-Control` or `Retry-After` can hold off fetching.
+const MAX_HOLD_OFF: Duration = Duration::from_hours(24);
 
 fn mutex_now() -> Mutex<Instant> {
 	Mutex::new(Instant::now())
 }
 
-/// HTTP GET an Atom feed, and subscribe via WebSub if available.
+/// HTTP GET an Atom, RSS or JSON feed, and subscribe via `WebSub` if available.
+///
+/// Fetches are conditional (`ETag`/`Last-Modified`), and not more frequent than upstream asks
+/// through `Cache-Control: max-age` or `Retry-After`.
 #[serde_as]
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct Feed {
 	url: Url,
 
+	/// Seconds between fetches.
 	#[serde_as(as = "DurationSeconds")]
+	#[schemars(with = "u64")]
 	ttl: Duration,
 	#[serde(skip, default = "mutex_now")]
 	last_fetch: Mutex<Instant>,
+	/// No fetching before this, as asked by upstream.
+	#[serde(skip)]
+	hold_off: Mutex<Option<Instant>>,
+	/// `None` until loaded from the database.
+	#[serde(skip)]
+	validators: Mutex<Option<Validators>>,
+	/// Where validators are persisted, set when the flow is built.
+	#[serde(skip)]
+	database: Option<(SqlitePool, String, i64)>,
 
 	#[serde(skip)]
 	web_sub: Mutex<Option<WebSub>>,
+	#[serde(skip)]
+	http: HttpClient,
 
 	#[serde(skip)]
 	input: Arc<IO>,
@@ -40,18 +76,215 @@ pub struct Feed {
 	output: Arc<IO>,
 }
 
+#[derive(Clone, Debug, Default)]
+struct Validators {
+	etag: Option<String>,
+	last_modified: Option<String>,
+}
+
+impl Validators {
+	fn from_headers(headers: &HeaderMap) -> Self {
+		let get = |name| {
+			headers
+				.get(name)
+				.and_then(|v| v.to_str().ok())
+				.map(String::from)
+		};
+
+		Self {
+			etag: get(ETAG),
+			last_modified: get(LAST_MODIFIED),
+		}
+	}
+
+	fn is_empty(&self) -> bool {
+		self.etag.is_none() && self.last_modified.is_none()
+	}
+}
+
+/// A full response.
+struct Fetched {
+	content: Bytes,
+	content_type: Option<String>,
+	web_sub: Option<WebSub>,
+}
+
+/// How long upstream asks us to wait, from `Cache-Control: max-age` and `Retry-After`.
+fn hold_off(headers: &HeaderMap) -> Option<Duration> {
+	let max_age = headers
+		.get_all(CACHE_CONTROL)
+		.iter()
+		.filter_map(|v| v.to_str().ok())
+		.flat_map(|v| v.split(','))
+		.find_map(|d| d.trim().strip_prefix("max-age=")?.parse().ok())
+		.map(Duration::from_secs);
+
+	let retry_after = headers
+		.get(RETRY_AFTER)
+		.and_then(|v| v.to_str().ok())
+		.and_then(|v| {
+			v.parse().ok().map(Duration::from_secs).or_else(|| {
+				DateTime::parse_from_rfc2822(v)
+					.ok()?
+					.signed_duration_since(Utc::now())
+					.to_std()
+					.ok()
+			})
+		});
+
+	max_age.max(retry_after).map(|d| d.min(MAX_HOLD_OFF))
+}
+
 impl Feed {
 	pub fn new(url: Url, ttl: Duration) -> Self {
 		Self {
 			url,
 			ttl,
 			last_fetch: mutex_now(),
+			hold_off: Mutex::default(),
+			validators: Mutex::default(),
+			database: None,
 			web_sub: Mutex::default(),
+			http: HttpClient::default(),
 
 			input: Arc::default(),
 			output: Arc::default(),
 		}
 	}
+
+	async fn validators(&self) -> anyhow::Result<Validators> {
+		if let Some(validators) = self.validators.lock().clone() {
+			return Ok(validators);
+		}
+
+		let mut validators = Validators::default();
+		if let Some((pool, flow, node)) = &self.database {
+			let url = self.url.as_str();
+			if let Some(record) = sqlx::query!(
+				"SELECT etag, last_modified FROM feed_cache WHERE flow = ? AND node = ? AND url = ?",
+				flow,
+				node,
+				url
+			)
+			.fetch_optional(pool)
+			.await?
+			{
+				validators.etag = record.etag;
+				validators.last_modified = record.last_modified;
+			}
+		}
+
+		self.validators.lock().replace(validators.clone());
+		Ok(validators)
+	}
+
+	/// Keeps the validators of a full response, with its body in case we restart before it changes.
+	async fn store(&self, validators: Validators, fetched: &Fetched) -> anyhow::Result<()> {
+		if let Some((pool, flow, node)) = &self.database {
+			if validators.is_empty() {
+				sqlx::query!(
+					"DELETE FROM feed_cache WHERE flow = ? AND node = ?",
+					flow,
+					node
+				)
+				.execute(pool)
+				.await?;
+			} else {
+				let url = self.url.as_str();
+				let content = fetched.content.as_ref();
+				sqlx::query!(
+					r#"
+					INSERT OR REPLACE INTO feed_cache (flow, node, url, etag, last_modified, content_type, content)
+					VALUES (?, ?, ?, ?, ?, ?, ?)
+					"#,
+					flow,
+					node,
+					url,
+					validators.etag,
+					validators.last_modified,
+					fetched.content_type,
+					content
+				)
+				.execute(pool)
+				.await?;
+			}
+		}
+
+		self.validators.lock().replace(validators);
+		Ok(())
+	}
+
+	/// The body stored with the validators, for a `304` when there is no output yet.
+	async fn cached(&self) -> anyhow::Result<Option<(Bytes, Option<String>)>> {
+		let Some((pool, flow, node)) = &self.database else {
+			return Ok(None);
+		};
+
+		Ok(sqlx::query!(
+			"SELECT content, content_type FROM feed_cache WHERE flow = ? AND node = ?",
+			flow,
+			node
+		)
+		.fetch_optional(pool)
+		.await?
+		.map(|r| (Bytes::from(r.content), r.content_type)))
+	}
+
+	/// GETs the feed, conditionally if it was fetched before. `None` if it was not modified.
+	async fn fetch(&self) -> anyhow::Result<Option<Fetched>> {
+		let validators = self.validators().await?;
+
+		let mut request = self.http.get(self.url.clone());
+		if let Some(etag) = &validators.etag {
+			request = request.header(IF_NONE_MATCH, etag);
+		}
+		if let Some(last_modified) = &validators.last_modified {
+			request = request.header(IF_MODIFIED_SINCE, last_modified);
+		}
+		let response = self.http.send(request).await?;
+
+		*self.hold_off.lock() = hold_off(response.headers()).map(|d| Instant::now() + d);
+
+		let web_sub = response
+			.headers()
+			.get(LINK)
+			.and_then(|v| v.to_str().ok())
+			.and_then(|v| WebSub::from_str(v).ok());
+
+		if response.status() == StatusCode::NOT_MODIFIED {
+			if self.output.is_some() {
+				return Ok(None);
+			}
+
+			// Restarted since the last full response.
+			let Some((content, content_type)) = self.cached().await? else {
+				self.validators.lock().replace(Validators::default());
+				return Err(anyhow!("Not modified, but nothing cached"));
+			};
+			return Ok(Some(Fetched {
+				content,
+				content_type,
+				web_sub,
+			}));
+		}
+
+		let response = response.error_for_status()?;
+		let validators = Validators::from_headers(response.headers());
+		let content_type = response
+			.headers()
+			.get(header::CONTENT_TYPE)
+			.and_then(|v| v.to_str().ok())
+			.map(ToString::to_string);
+
+		let fetched = Fetched {
+			content: self.http.bytes(response).await?,
+			content_type,
+			web_sub,
+		};
+		self.store(validators, &fetched).await?;
+
+		Ok(Some(fetched))
+	}
 }
 
 #[async_trait]
@@ -73,43 +306,56 @@ impl NodeTrait for Feed {
 	}
 
 	fn is_dirty(&self) -> bool {
-		!self.output.is_some()
-			|| self.input.is_dirty()
-			|| self.last_fetch.lock().elapsed() > self.ttl
+		if self.input.is_dirty() {
+			return true;
+		}
+		if self.hold_off.lock().is_some_and(|t| Instant::now() < t) {
+			return false;
+		}
+
+		!self.output.is_some() || self.last_fetch.lock().elapsed() > self.ttl
 	}
 
-	#[tracing::instrument(name = "feed_node")]
+	#[tracing::instrument(name = "feed_node", skip(self), fields(url = %self.url))]
 	async fn run(&self) -> anyhow::Result<()> {
 		let mut ws = self.input.is_dirty();
 
-		let content = if ws {
+		let (content, content_type) = if ws {
 			let Some(Data::WebSub(websub)) = self.input.get() else {
 				return Err(anyhow!(""));
 			};
 
-			websub
+			(websub, None)
 		} else {
-			let response = reqwest::get(self.url.clone()).await?;
-
-			if let Some(ct) = response.headers().get(header::CONTENT_TYPE) {
-				if ct.eq("application/rss+xml") {
-					// TODO: Handle RSS channels (upgrade to atom)
-				}
-			}
+			let Some(fetched) = self.fetch().await? else {
+				// Not modified: the output stays as it is, and downstream nodes don't rerun.
+				*self.last_fetch.lock() = Instant::now();
+				return Ok(());
+			};
 
-			if let Some(websub) = response
-				.headers()
-				.get(LINK)
-				.and_then(|v| v.to_str().ok())
-				.and_then(|v| WebSub::from_str(v).ok())
-			{
+			if let Some(websub) = fetched.web_sub {
 				self.web_sub.lock().replace(websub);
 				ws = true;
 			}
 
-			response.bytes().await?
+			(fetched.content, fetched.content_type)
+		};
+		let previous = self.output.get();
+		let fallback = Undated {
+			previous: match &previous {
+				Some(Data::Feed(feed)) => Some(feed),
+				_ => None,
+			},
+			// Pushes come without headers.
+			modified: self
+				.validators
+				.lock()
+				.as_ref()
+				.filter(|_| !self.input.is_dirty())
+				.and_then(|v| v.last_modified.as_deref())
+				.and_then(|d| DateTime::parse_from_rfc2822(d).ok()),
 		};
-		let feed = atom_syndication::Feed::read_from(&content[..])?;
+		let feed = feed::parse(&content, content_type.as_deref(), fallback)?;
 
 		if !ws {
 			let hub = feed.links.iter().find(|l| l.rel.eq("hub"));
@@ -134,30 +380,150 @@ impl NodeTrait for Feed {
 		self.output = output;
 	}
 
+	fn init(&mut self, index: usize, context: &Context) {
+		self.http = context.http.clone();
+
+		if let (Some(flow), Some(pool)) = (&context.flow, &context.pool) {
+			self.database = Some((
+				pool.clone(),
+				flow.clone(),
+				i64::try_from(index).unwrap_or_default(),
+			));
+		}
+	}
+
 	fn web_sub(&self) -> Option<WebSub> {
 		self.web_sub.lock().clone()
 	}
+
+	fn next_run(&self) -> Option<Instant> {
+		let due = if self.output.is_some() {
+			*self.last_fetch.lock() + self.ttl
+		} else {
+			Instant::now()
+		};
+
+		Some(self.hold_off.lock().map_or(due, |t| t.max(due)))
+	}
 }
 
 #[cfg(test)]
 mod test {
 	use std::time::Duration;
 
-	use crate::flow::{feed::Feed, node::NodeTrait};
+	use axum::{
+		http::{header, HeaderMap, StatusCode},
+		response::IntoResponse,
+		routing::get,
+		Router,
+	};
+	use reqwest::header::HeaderValue;
+	use tokio::net::TcpListener;
+
+	use super::hold_off;
+	use crate::{
+		flow::{feed::Feed, node::NodeTrait, Context},
+		http::HttpClient,
+	};
+
+	const ATOM: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+	<title>Push test</title>
+	<id>urn:test</id>
+	<updated>2024-07-02T10:00:00Z</updated>
+	<link rel="hub" href="https://hub.example.com/"/>
+	<link rel="self" href="https://example.com/feed"/>
+</feed>"#;
 
 	#[tokio::test]
 	pub async fn websub() -> anyhow::Result<()> {
-		let node = Feed::new(
-			"http://push-tester.cweiske.de/feed.php".parse().unwrap(),
-			Duration::from_secs(60 * 60),
-		);
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let url = format!("http://{}/feed", listener.local_addr()?);
+		tokio::spawn(async move {
+			let router = Router::new().route(
+				"/feed",
+				get(|| async { ([(header::CONTENT_TYPE, "application/atom+xml")], ATOM) }),
+			);
+			axum::serve(listener, router).await
+		});
+
+		let node = Feed::new(url.parse()?, Duration::from_hours(1));
+		node.run().await?;
+
+		let web_sub = node.web_sub().expect("hub and self links are discovered");
+		assert_eq!(web_sub.hub, "https://hub.example.com/");
+		assert_eq!(web_sub.topic, "https://example.com/feed");
 
+		Ok(())
+	}
+
+	async fn conditional(headers: HeaderMap) -> impl IntoResponse {
+		if headers
+			.get(header::IF_NONE_MATCH)
+			.is_some_and(|v| v == "\"v1\"")
+		{
+			return StatusCode::NOT_MODIFIED.into_response();
+		}
+
+		(
+			[
+				(header::CONTENT_TYPE, "application/atom+xml"),
+				(header::ETAG, "\"v1\""),
+			],
+			ATOM,
+		)
+			.into_response()
+	}
+
+	#[tokio::test]
+	pub async fn conditional_get() -> anyhow::Result<()> {
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let url = format!("http://{}/feed", listener.local_addr()?);
+		tokio::spawn(async move {
+			axum::serve(listener, Router::new().route("/feed", get(conditional))).await
+		});
+
+		let pool = crate::test::pool().await?;
+		let context = Context::new("flow", pool, HttpClient::default());
+
+		let mut node = Feed::new(url.parse()?, Duration::ZERO);
+		node.init(0, &context);
 		node.run().await?;
+		assert!(node.output.is_dirty());
+		node.output.clear();
 
-		let c = node.web_sub();
+		// Not modified: nothing to parse, and nothing for downstream nodes to do.
+		node.run().await?;
+		assert!(node.output.is_some());
+		assert!(!node.output.is_dirty());
 
-		println!("{c:?}");
+		// After a restart, the stored response stands in for the one not sent.
+		let mut node = Feed::new(url.parse()?, Duration::ZERO);
+		node.init(0, &context);
+		node.run().await?;
+		assert!(node.output.is_some());
 
 		Ok(())
 	}
+
+	#[test]
+	pub fn hold_off_headers() {
+		let mut headers = HeaderMap::new();
+		assert_eq!(hold_off(&headers), None);
+
+		headers.insert(
+			header::CACHE_CONTROL,
+			HeaderValue::from_static("public, max-age=600"),
+		);
+		assert_eq!(hold_off(&headers), Some(Duration::from_mins(10)));
+
+		headers.insert(header::RETRY_AFTER, HeaderValue::from_static("3600"));
+		assert_eq!(hold_off(&headers), Some(Duration::from_hours(1)));
+
+		headers.insert(
+			header::CACHE_CONTROL,
+			HeaderValue::from_static("max-age=31536000"),
+		);
+		assert_eq!(hold_off(&headers), Some(Duration::from_hours(24)));
+	}
 }
@@ -3,13 +3,14 @@ use std::{slice, sync::Arc};
 use anyhow::anyhow;
 use async_trait::async_trait;
 use regex::Regex;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_regex;
 
 use super::node::{Data, DataKind, Field, NodeTrait, IO};
 
 /// Filter out specific entries
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct Filter {
 	field: Field,
 	#[allow(clippy::struct_field_names)]
@@ -23,6 +24,7 @@ pub struct Filter {
 }
 
 impl Filter {
+	#[cfg(test)]
 	pub fn new(field: Field, filter: Kind, invert: bool) -> Self {
 		Self {
 			field,
@@ -42,7 +44,7 @@ impl NodeTrait for Filter {
 	}
 
 	fn outputs(&self) -> &[Arc<IO>] {
-		slice::from_ref(&self.input)
+		slice::from_ref(&self.output)
 	}
 
 	fn input_types(&self) -> &[DataKind] {
@@ -94,9 +96,13 @@ impl NodeTrait for Filter {
 	}
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum Kind {
-	Regex(#[serde(with = "serde_regex")] Regex),
+	Regex(
+		#[serde(with = "serde_regex")]
+		#[schemars(with = "String")]
+		Regex,
+	),
 	Contains(String),
 }
@@ -0,0 +1,131 @@
+use std::collections::{HashSet, VecDeque};
+
+use super::Connection;
+
+/// Node dependency graph of a [`Flow`](super::Flow), derived from its connections.
+#[derive(Debug, Default)]
+pub struct Graph {
+	upstream: Box<[Box<[usize]>]>,
+	downstream: Box<[Box<[usize]>]>,
+}
+
+impl Graph {
+	/// Builds the graph for `len` nodes, ignoring connections to nodes that do not exist.
+	pub fn new(len: usize, connections: &[Connection]) -> Self {
+		let mut upstream = vec![Vec::new(); len];
+		let mut downstream = vec![Vec::new(); len];
+
+		for Connection(from, to) in connections {
+			if from.0 >= len || to.0 >= len {
+				continue;
+			}
+
+			if !upstream[to.0].contains(&from.0) {
+				upstream[to.0].push(from.0);
+				downstream[from.0].push(to.0);
+			}
+		}
+
+		Self {
+			upstream: upstream.into_iter().map(Vec::into_boxed_slice).collect(),
+			downstream: downstream.into_iter().map(Vec::into_boxed_slice).collect(),
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.upstream.len()
+	}
+
+	/// Nodes whose outputs are connected to the inputs of `node`.
+	pub fn upstream(&self, node: usize) -> &[usize] {
+		&self.upstream[node]
+	}
+
+	/// Nodes whose inputs are connected to the outputs of `node`.
+	pub fn downstream(&self, node: usize) -> &[usize] {
+		&self.downstream[node]
+	}
+
+	/// Topological order of the nodes (Kahn's algorithm).
+	/// Nodes that are part of, or come after a cycle are left out.
+	pub fn sorted(&self) -> Vec<usize> {
+		Self::peel(&self.upstream, &self.downstream)
+	}
+
+	/// Nodes that are part of a cycle, in index order.
+	pub fn cyclic(&self) -> Vec<usize> {
+		let forward: HashSet<usize> = self.sorted().into_iter().collect();
+		if forward.len() == self.len() {
+			return Vec::new();
+		}
+
+		// Peeling the graph from both ends leaves only the nodes on (or between) cycles.
+		let backward: HashSet<usize> = Self::peel(&self.downstream, &self.upstream)
+			.into_iter()
+			.collect();
+		(0..self.len())
+			.filter(|n| !forward.contains(n) && !backward.contains(n))
+			.collect()
+	}
+
+	fn peel(upstream: &[Box<[usize]>], downstream: &[Box<[usize]>]) -> Vec<usize> {
+		let mut pending: Vec<usize> = upstream.iter().map(|u| u.len()).collect();
+		let mut ready: VecDeque<usize> = (0..upstream.len()).filter(|i| pending[*i] == 0).collect();
+
+		let mut sorted = Vec::with_capacity(upstream.len());
+		while let Some(node) = ready.pop_front() {
+			sorted.push(node);
+
+			for next in &*downstream[node] {
+				pending[*next] -= 1;
+				if pending[*next] == 0 {
+					ready.push_back(*next);
+				}
+			}
+		}
+
+		sorted
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::Graph;
+	use crate::flow::{Connection, Port};
+
+	fn connect(from: usize, to: usize) -> Connection {
+		Connection(Port(from, 0), Port(to, 0))
+	}
+
+	#[test]
+	pub fn sorted() {
+		// Two branches, 0 -> 1 and 2 -> 3, merging into 4.
+		let graph = Graph::new(
+			5,
+			&[connect(3, 4), connect(1, 4), connect(0, 1), connect(2, 3)],
+		);
+
+		let sorted = graph.sorted();
+		let position = |n: usize| sorted.iter().position(|i| *i == n).unwrap();
+
+		assert_eq!(sorted.len(), 5);
+		assert!(position(0) < position(1));
+		assert!(position(2) < position(3));
+		assert!(position(1) < position(4));
+		assert!(position(3) < position(4));
+		assert_eq!(graph.upstream(4), &[3, 1]);
+		assert!(graph.cyclic().is_empty());
+	}
+
+	#[test]
+	pub fn cycle() {
+		let graph = Graph::new(
+			4,
+			&[connect(0, 1), connect(1, 2), connect(2, 1), connect(2, 3)],
+		);
+		assert_eq!(graph.cyclic(), [1, 2]);
+
+		let graph = Graph::new(1, &[connect(0, 0)]);
+		assert_eq!(graph.cyclic(), [0]);
+	}
+}
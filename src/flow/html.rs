@@ -6,33 +6,52 @@ use std::{
 
 use anyhow::anyhow;
 use async_trait::async_trait;
+use atom_syndication::{Content, Entry, FixedDateTime, Link, Text};
+use chrono::{DateTime, NaiveDate};
+use encoding_rs::{Encoding, UTF_8};
 use parking_lot::Mutex;
-use reqwest::header;
-use scraper::Selector;
+use reqwest::header::{CONTENT_TYPE, LAST_MODIFIED};
+use schemars::JsonSchema;
+use scraper::{ElementRef, Selector};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DurationSeconds};
+use sha2::{Digest, Sha256};
 use url::Url;
 
-use super::node::{Data, DataKind, NodeTrait, IO};
-use crate::subscriber::websub::WebSub;
+use super::{
+	node::{Data, DataKind, NodeTrait, IO},
+	serde_selector, Context,
+};
+use crate::{
+	feed::{self, Undated},
+	http::HttpClient,
+	subscriber::websub::WebSub,
+};
 
 fn mutex_now() -> Mutex<Instant> {
 	Mutex::new(Instant::now())
 }
 
-/// HTTP GET an HTML page, and subscribe via WebSub if available.
+/// HTTP GET an HTML page and scrape it into a feed, and subscribe via `WebSub` if available.
 #[serde_as]
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct Html {
 	url: Url,
 
+	/// Seconds between fetches.
 	#[serde_as(as = "DurationSeconds")]
+	#[schemars(with = "u64")]
 	ttl: Duration,
 	#[serde(skip, default = "mutex_now")]
 	last_fetch: Mutex<Instant>,
 
+	#[serde(flatten)]
+	selectors: Selectors,
+
 	#[serde(skip)]
 	web_sub: Mutex<Option<WebSub>>,
+	#[serde(skip)]
+	http: HttpClient,
 
 	#[serde(skip)]
 	input: Arc<IO>,
@@ -40,20 +59,216 @@ pub struct Html {
 	output: Arc<IO>,
 }
 
+/// CSS selectors locating entries in the page.
+///
+/// All but `entry` are matched within each entry container.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct Selectors {
+	/// Container of a single entry, e.g. `article`.
+	#[serde(with = "serde_selector")]
+	#[schemars(with = "String")]
+	pub entry: Selector,
+	#[serde(with = "serde_selector")]
+	#[schemars(with = "String")]
+	pub title: Selector,
+	/// Element with the entry's `href`. Defaults to the container if it is a link, else its first link.
+	#[serde(
+		default,
+		with = "serde_selector::option",
+		skip_serializing_if = "Option::is_none"
+	)]
+	#[schemars(with = "Option<String>")]
+	pub link: Option<Selector>,
+	/// Element with a `datetime` attribute, or an RFC 3339, RFC 2822 or `YYYY-MM-DD` date as text.
+	#[serde(
+		default,
+		with = "serde_selector::option",
+		skip_serializing_if = "Option::is_none"
+	)]
+	#[schemars(with = "Option<String>")]
+	pub date: Option<Selector>,
+	#[serde(
+		default,
+		with = "serde_selector::option",
+		skip_serializing_if = "Option::is_none"
+	)]
+	#[schemars(with = "Option<String>")]
+	pub summary: Option<Selector>,
+	#[serde(
+		default,
+		with = "serde_selector::option",
+		skip_serializing_if = "Option::is_none"
+	)]
+	#[schemars(with = "Option<String>")]
+	pub content: Option<Selector>,
+}
+
 impl Html {
-	pub fn new(url: Url, ttl: Duration) -> Self {
-		Self {
-			url,
-			ttl,
-			last_fetch: mutex_now(),
-			web_sub: Mutex::default(),
-
-			input: Arc::default(),
-			output: Arc::default(),
+	/// Turns every match of the entry selector into an entry, see [`Undated`] for entries without
+	/// a date.
+	fn scrape(&self, html: &scraper::Html, fallback: Undated) -> atom_syndication::Feed {
+		let entries: Vec<Entry> = html
+			.select(&self.selectors.entry)
+			.filter_map(|container| self.entry(container, fallback))
+			.collect();
+
+		let title = Selector::parse("title")
+			.ok()
+			.and_then(|s| html.select(&s).next())
+			.map(text)
+			.filter(|t| !t.is_empty())
+			.unwrap_or_else(|| self.url.to_string());
+
+		atom_syndication::Feed {
+			title: Text::plain(title),
+			id: self.url.to_string(),
+			updated: entries
+				.iter()
+				.map(|e| e.updated)
+				.max()
+				.unwrap_or_else(|| fallback.feed()),
+			links: vec![link(self.url.to_string(), "alternate")],
+			entries,
+			..Default::default()
+		}
+	}
+
+	fn entry(&self, container: ElementRef, fallback: Undated) -> Option<Entry> {
+		let select = |selector: &Selector| container.select(selector).next();
+
+		let title = select(&self.selectors.title).map(text).unwrap_or_default();
+		let href = if let Some(selector) = &self.selectors.link {
+			select(selector).and_then(|e| e.attr("href"))
+		} else {
+			container.attr("href").or_else(|| {
+				Selector::parse("a[href]")
+					.ok()
+					.and_then(|s| container.select(&s).next())
+					.and_then(|e| e.attr("href"))
+			})
 		}
+		.and_then(|href| self.url.join(href).ok());
+
+		if title.is_empty() && href.is_none() {
+			return None;
+		}
+
+		let date =
+			self.selectors.date.as_ref().and_then(select).and_then(|e| {
+				parse_date(&e.attr("datetime").map_or_else(|| text(e), String::from))
+			});
+		let summary = self
+			.selectors
+			.summary
+			.as_ref()
+			.and_then(select)
+			.map(|e| e.inner_html().trim().to_string());
+		let content = self
+			.selectors
+			.content
+			.as_ref()
+			.and_then(select)
+			.map(|e| e.inner_html().trim().to_string());
+
+		// Pages rarely carry ids, links are the most stable thing they do carry.
+		let id = href.as_ref().map_or_else(
+			|| {
+				let digest = Sha256::new()
+					.chain_update(self.url.as_str())
+					.chain_update(&title)
+					.finalize();
+				format!("urn:sha256:{}", hex::encode(digest))
+			},
+			ToString::to_string,
+		);
+		let updated = date.unwrap_or_else(|| fallback.entry(&id, None));
+
+		Some(Entry {
+			title: Text::plain(title),
+			id,
+			updated,
+			published: date,
+			links: href
+				.map(|href| vec![link(href.to_string(), "alternate")])
+				.unwrap_or_default(),
+			summary: summary.map(|value| Text {
+				value,
+				r#type: atom_syndication::TextType::Html,
+				..Default::default()
+			}),
+			content: content.map(|value| Content {
+				value: Some(value),
+				content_type: Some("html".to_string()),
+				..Default::default()
+			}),
+			..Default::default()
+		})
 	}
 }
 
+/// Text of an element, with whitespace collapsed.
+fn text(element: ElementRef) -> String {
+	element
+		.text()
+		.flat_map(str::split_whitespace)
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+fn link(href: String, rel: &str) -> Link {
+	Link {
+		href,
+		rel: rel.to_string(),
+		..Default::default()
+	}
+}
+
+/// How much of a page is searched for a `<meta>` charset.
+const SNIFF_LEN: usize = 1024;
+
+/// Decodes a page by its byte order mark, the `Content-Type` charset or a `<meta>` charset, in
+/// that order, falling back to UTF-8. Malformed sequences are replaced rather than failing.
+fn decode(content: &[u8], content_type: Option<&str>) -> String {
+	let encoding = Encoding::for_bom(content)
+		.map(|(encoding, _)| encoding)
+		.or_else(|| content_type.and_then(charset).and_then(label))
+		.or_else(|| meta_charset(content))
+		.unwrap_or(UTF_8);
+
+	encoding.decode_with_bom_removal(content).0.into_owned()
+}
+
+fn label(label: &str) -> Option<&'static Encoding> {
+	Encoding::for_label(label.trim().trim_matches(['"', '\'']).as_bytes())
+}
+
+/// The `charset` parameter of a `Content-Type`.
+fn charset(content_type: &str) -> Option<&str> {
+	content_type.split(';').skip(1).find_map(|param| {
+		let (name, value) = param.split_once('=')?;
+		name.trim().eq_ignore_ascii_case("charset").then_some(value)
+	})
+}
+
+/// Charset declared by `<meta charset>` or `<meta http-equiv="Content-Type">` near the start of
+/// the page, which is ASCII in every encoding pages declare this way.
+fn meta_charset(content: &[u8]) -> Option<&'static Encoding> {
+	let prefix = String::from_utf8_lossy(&content[..content.len().min(SNIFF_LEN)]).to_lowercase();
+	let start = prefix.find("charset=")? + "charset=".len();
+	let value = prefix[start..].trim_start_matches(['"', '\'']);
+	let end = value
+		.find(|c: char| !(c.is_ascii_alphanumeric() || "-_.:".contains(c)))
+		.unwrap_or(value.len());
+	label(&value[..end])
+}
+
+fn parse_date(date: &str) -> Option<FixedDateTime> {
+	feed::parse_date(date).or_else(|| {
+		let date = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok()?;
+		Some(date.and_hms_opt(0, 0, 0)?.and_utc().fixed_offset())
+	})
+}
+
 #[async_trait]
 impl NodeTrait for Html {
 	fn inputs(&self) -> &[Arc<IO>] {
@@ -78,41 +293,68 @@ impl NodeTrait for Html {
 			|| self.last_fetch.lock().elapsed() > self.ttl
 	}
 
-	#[tracing::instrument(name = "html_node")]
+	#[tracing::instrument(name = "html_node", skip(self))]
 	async fn run(&self) -> anyhow::Result<()> {
 		let sub = self.input.is_dirty();
-		let content = if sub {
+		let (content, content_type, modified) = if sub {
 			let Some(Data::WebSub(websub)) = self.input.get() else {
 				return Err(anyhow!(""));
 			};
 
-			websub
+			(websub, None, None)
 		} else {
-			let response = reqwest::get(self.url.clone()).await?;
+			let response = self
+				.http
+				.send(self.http.get(self.url.clone()))
+				.await?
+				.error_for_status()?;
+			let header = |name| {
+				response
+					.headers()
+					.get(name)
+					.and_then(|v| v.to_str().ok())
+					.map(String::from)
+			};
+			let content_type = header(CONTENT_TYPE);
+			let modified =
+				header(LAST_MODIFIED).and_then(|d| DateTime::parse_from_rfc2822(&d).ok());
 
-			response.bytes().await?
+			(self.http.bytes(response).await?, content_type, modified)
+		};
+		let previous = self.output.get();
+		let fallback = Undated {
+			previous: match &previous {
+				Some(Data::Feed(feed)) => Some(feed),
+				_ => None,
+			},
+			modified,
 		};
-		let html = scraper::Html::parse_document(std::str::from_utf8(&content)?);
 
-		let mut links = html.select(&Selector::parse("link").map_err(|e| anyhow!(e.to_string()))?);
+		// `scraper::Html` is not `Send`, so it must not live across an await point.
+		let (mut feed, web_sub) = {
+			let html = scraper::Html::parse_document(&decode(&content, content_type.as_deref()));
 
-		if !sub {
-			let hub = links
-				.clone()
-				.find(|l| l.attr("rel").map(|a| a.eq("hub")).unwrap_or_default());
-			let this = links.find(|l| l.attr("rel").map(|a| a.eq("self")).unwrap_or_default());
+			let links = Selector::parse("link").map_err(|e| anyhow!(e.to_string()))?;
+			let rel = |rel: &str| {
+				html.select(&links)
+					.find(|l| l.attr("rel").is_some_and(|a| a.eq(rel)))
+					.and_then(|l| l.attr("href"))
+					.map(String::from)
+			};
 
-			if let (Some(hub), Some(this)) = (hub, this) {
-				self.web_sub.lock().replace(WebSub {
-					hub: hub.attr("href").unwrap_or_default().to_string(),
-					topic: this.attr("href").unwrap_or_default().to_string(),
-				});
+			(self.scrape(&html, fallback), rel("hub").zip(rel("self")))
+		};
+
+		if let Some((hub, topic)) = web_sub {
+			feed.links.push(link(hub.clone(), "hub"));
+			feed.links.push(link(topic.clone(), "self"));
+			if !sub {
+				self.web_sub.lock().replace(WebSub { topic, hub });
 			}
 		}
 
-		todo!();
 		*self.last_fetch.lock() = Instant::now();
-		self.output.accept(todo!())
+		self.output.accept(feed)
 	}
 
 	fn set_input(&mut self, _index: usize, input: Arc<IO>) {
@@ -122,7 +364,175 @@ impl NodeTrait for Html {
 		self.output = output;
 	}
 
+	fn init(&mut self, _index: usize, context: &Context) {
+		self.http = context.http.clone();
+	}
+
 	fn web_sub(&self) -> Option<WebSub> {
 		self.web_sub.lock().clone()
 	}
+
+	fn next_run(&self) -> Option<Instant> {
+		if self.output.is_some() {
+			Some(*self.last_fetch.lock() + self.ttl)
+		} else {
+			Some(Instant::now())
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::{
+		sync::{
+			atomic::{AtomicBool, Ordering},
+			Arc,
+		},
+		time::Duration,
+	};
+
+	use axum::{http::StatusCode, routing::get, Router};
+	use tokio::net::TcpListener;
+
+	use super::{decode, Html};
+	use crate::{
+		feed::Undated,
+		flow::node::{Data, NodeTrait},
+	};
+
+	const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head><title> Example   blog </title></head>
+<body>
+	<article>
+		<h2><a href="/posts/1">First post</a></h2>
+		<time datetime="2024-07-02T10:00:00Z">July 2nd</time>
+		<p class="summary">The <b>first</b> one.</p>
+	</article>
+	<article>
+		<h2>Second post</h2>
+		<span class="date">2024-07-03</span>
+	</article>
+	<article></article>
+</body>
+</html>"#;
+
+	#[test]
+	pub fn scrape() -> anyhow::Result<()> {
+		let node: Html = serde_json::from_value(serde_json::json!({
+			"url": "https://example.com/blog/",
+			"ttl": 3600,
+			"entry": "article",
+			"title": "h2",
+			"date": "time, .date",
+			"summary": ".summary",
+		}))?;
+
+		let feed = node.scrape(&scraper::Html::parse_document(PAGE), Undated::default());
+		assert_eq!(feed.title.value, "Example blog");
+		assert_eq!(feed.entries.len(), 2, "empty containers are skipped");
+
+		let first = &feed.entries[0];
+		assert_eq!(first.id, "https://example.com/posts/1");
+		assert_eq!(first.title.value, "First post");
+		assert_eq!(
+			first.summary.as_ref().map(|s| s.value.as_str()),
+			Some("The <b>first</b> one.")
+		);
+		assert_eq!(first.updated.to_rfc3339(), "2024-07-02T10:00:00+00:00");
+
+		let second = &feed.entries[1];
+		assert!(second.id.starts_with("urn:sha256:"));
+		assert_eq!(second.updated.to_rfc3339(), "2024-07-03T00:00:00+00:00");
+
+		// Ids of link-less entries are derived from the page and title, so they survive refetches.
+		let again = node.scrape(&scraper::Html::parse_document(PAGE), Undated::default());
+		assert_eq!(again.entries[1].id, second.id);
+		assert_eq!(again.entries[1].updated, second.updated);
+
+		// Entries without a date keep the one they got first, from `Last-Modified` here.
+		let undated = scraper::Html::parse_document(
+			r#"<article><h2><a href="/posts/3">Third post</a></h2></article>"#,
+		);
+		let modified = chrono::DateTime::parse_from_rfc2822("Tue, 02 Jul 2024 10:00:00 GMT")?;
+		let first = node.scrape(
+			&undated,
+			Undated {
+				previous: None,
+				modified: Some(modified),
+			},
+		);
+		assert_eq!(first.entries[0].updated, modified);
+		assert_eq!(first.updated, modified);
+		let again = node.scrape(
+			&undated,
+			Undated {
+				previous: Some(&first),
+				modified: None,
+			},
+		);
+		assert_eq!(again.entries[0].updated, modified);
+
+		assert_eq!(node.ttl, Duration::from_hours(1));
+		Ok(())
+	}
+
+	#[test]
+	pub fn decode_charsets() {
+		let latin1 =
+			b"<html><head><meta charset=\"ISO-8859-1\"><title>Caf\xe9</title></head></html>";
+		assert!(decode(latin1, None).contains("Caf\u{e9}"));
+
+		let sjis = b"<html><head><title>\x93\xfa\x96\x7b</title></head></html>";
+		assert!(decode(sjis, Some("text/html; charset=Shift_JIS")).contains("\u{65e5}\u{672c}"));
+
+		let invalid = b"<title>Caf\xe9</title>";
+		assert_eq!(decode(invalid, None), "<title>Caf\u{fffd}</title>");
+	}
+
+	#[tokio::test]
+	pub async fn error_status() -> anyhow::Result<()> {
+		let failing = Arc::new(AtomicBool::new(false));
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let url = format!("http://{}/blog/", listener.local_addr()?);
+		let router = Router::new().route(
+			"/blog/",
+			get({
+				let failing = failing.clone();
+				|| async move {
+					if failing.load(Ordering::SeqCst) {
+						(
+							StatusCode::SERVICE_UNAVAILABLE,
+							"<h2>Down for maintenance</h2>",
+						)
+					} else {
+						(StatusCode::OK, PAGE)
+					}
+				}
+			}),
+		);
+		tokio::spawn(async move { axum::serve(listener, router).await });
+
+		let node: Html = serde_json::from_value(serde_json::json!({
+			"url": url,
+			"ttl": 3600,
+			"entry": "article",
+			"title": "h2",
+		}))?;
+
+		node.run().await?;
+		let Some(Data::Feed(good)) = node.outputs()[0].get() else {
+			panic!("Html node produced no feed");
+		};
+
+		// Error pages fail the run rather than being scraped over the last good output.
+		failing.store(true, Ordering::SeqCst);
+		assert!(node.run().await.is_err());
+		let Some(Data::Feed(kept)) = node.outputs()[0].get() else {
+			panic!("Html node lost its feed");
+		};
+		assert_eq!(kept.entries.len(), good.entries.len());
+
+		Ok(())
+	}
 }
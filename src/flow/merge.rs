@@ -0,0 +1,206 @@
+use std::{cmp::Reverse, collections::HashSet, sync::Arc};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use atom_syndication::{LinkBuilder, Text};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{
+	node::{Data, DataKind, NodeTrait, IO},
+	Context,
+};
+
+/// Most feeds a `Merge` may combine, so a flow can't have it allocate inputs without bound.
+pub const MAX_INPUTS: usize = 64;
+
+/// Combines several feeds into one, newest entries first.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct Merge {
+	/// Number of feeds to merge, at most [`MAX_INPUTS`].
+	count: usize,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	title: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	id: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	subtitle: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	link: Option<Url>,
+
+	#[serde(skip)]
+	input_types: Box<[DataKind]>,
+	#[serde(skip)]
+	inputs: Vec<Arc<IO>>,
+	#[serde(skip)]
+	output: Arc<IO>,
+}
+
+impl Merge {
+	#[cfg(test)]
+	pub fn new(count: usize) -> Self {
+		Self {
+			count,
+			title: None,
+			id: None,
+			subtitle: None,
+			link: None,
+
+			input_types: Box::default(),
+			inputs: Vec::new(),
+			output: Arc::default(),
+		}
+	}
+
+	pub fn count(&self) -> usize {
+		self.count
+	}
+
+	fn merge(&self, feeds: Vec<atom_syndication::Feed>) -> Option<atom_syndication::Feed> {
+		let mut feeds = feeds.into_iter();
+		let mut merged = feeds.next()?;
+
+		// Links of the first feed (`self`, `hub`, ...) describe that feed, not the merged one.
+		merged.links.clear();
+		merged.entries.extend(feeds.flat_map(|f| f.entries));
+
+		// Newest first, so that only the most recent version of a duplicated entry is kept.
+		merged
+			.entries
+			.sort_by_key(|e| Reverse((e.updated, e.published)));
+		let mut ids = HashSet::new();
+		merged.entries.retain(|e| ids.insert(e.id.clone()));
+
+		if let Some(updated) = merged.entries.iter().map(|e| e.updated).max() {
+			merged.updated = updated;
+		}
+		if let Some(title) = &self.title {
+			merged.title = Text::plain(title.clone());
+		}
+		if let Some(id) = &self.id {
+			merged.id.clone_from(id);
+		}
+		if let Some(subtitle) = &self.subtitle {
+			merged.subtitle = Some(Text::plain(subtitle.clone()));
+		}
+		if let Some(link) = &self.link {
+			merged
+				.links
+				.push(LinkBuilder::default().href(link.to_string()).build());
+		}
+
+		Some(merged)
+	}
+}
+
+#[async_trait]
+impl NodeTrait for Merge {
+	fn inputs(&self) -> &[Arc<IO>] {
+		&self.inputs
+	}
+
+	fn outputs(&self) -> &[Arc<IO>] {
+		std::slice::from_ref(&self.output)
+	}
+
+	fn input_types(&self) -> &[DataKind] {
+		&self.input_types
+	}
+
+	fn output_types(&self) -> &[DataKind] {
+		&[DataKind::Feed]
+	}
+
+	#[tracing::instrument(name = "merge_node", skip(self))]
+	async fn run(&self) -> anyhow::Result<()> {
+		let feeds = self
+			.inputs
+			.iter()
+			.filter_map(|i| match i.get() {
+				Some(Data::Feed(feed)) => Some(feed),
+				_ => None,
+			})
+			.collect();
+
+		let Some(merged) = self.merge(feeds) else {
+			return Err(anyhow!("Input data not available"));
+		};
+
+		self.output.accept(merged)
+	}
+
+	fn set_input(&mut self, index: usize, input: Arc<IO>) {
+		if let Some(slot) = self.inputs.get_mut(index) {
+			*slot = input;
+		}
+	}
+	fn set_output(&mut self, _index: usize, output: Arc<IO>) {
+		self.output = output;
+	}
+
+	fn init(&mut self, _index: usize, _context: &Context) {
+		// Left without inputs, validation rejects the flow.
+		if self.count > MAX_INPUTS {
+			return;
+		}
+
+		self.input_types = vec![DataKind::Feed; self.count].into();
+		self.inputs = (0..self.count)
+			.map(|_| Arc::new(IO::new(DataKind::Feed)))
+			.collect();
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use atom_syndication::{Entry, Feed, FixedDateTime};
+
+	use super::Merge;
+	use crate::flow::{
+		node::{Data, NodeTrait},
+		Context,
+	};
+
+	fn entry(id: &str, title: &str, updated: &str) -> Entry {
+		Entry {
+			id: id.to_string(),
+			title: title.into(),
+			updated: FixedDateTime::parse_from_rfc3339(updated).unwrap(),
+			..Default::default()
+		}
+	}
+
+	#[tokio::test]
+	pub async fn merge() -> anyhow::Result<()> {
+		let mut node = Merge::new(2);
+		node.init(0, &Context::default());
+
+		node.inputs()[0].accept(Feed {
+			entries: vec![
+				entry("a", "A", "2024-01-01T00:00:00Z"),
+				entry("b", "B (old)", "2024-01-02T00:00:00Z"),
+			],
+			..Default::default()
+		})?;
+		node.inputs()[1].accept(Feed {
+			entries: vec![
+				entry("b", "B (new)", "2024-01-04T00:00:00Z"),
+				entry("c", "C", "2024-01-03T00:00:00Z"),
+			],
+			..Default::default()
+		})?;
+		node.run().await?;
+
+		let Some(Data::Feed(feed)) = node.outputs()[0].get() else {
+			panic!("Merge produced no feed");
+		};
+		let titles: Vec<_> = feed.entries.iter().map(|e| e.title.as_str()).collect();
+
+		assert_eq!(titles, ["B (new)", "C", "A"]);
+		assert_eq!(feed.updated, feed.entries[0].updated);
+
+		Ok(())
+	}
+}
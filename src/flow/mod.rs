@@ -1,106 +1,221 @@
 use std::{
-	collections::HashMap,
+	collections::{BTreeMap, HashMap, HashSet, VecDeque},
 	fmt::{Display, Formatter},
+	num::NonZeroUsize,
 	sync::Arc,
+	thread::available_parallelism,
+	time::{Duration, Instant},
 };
 
 use async_trait::async_trait;
+use futures::{stream::FuturesUnordered, StreamExt};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
 use tokio::sync::Mutex;
 
 pub mod ai;
+pub mod catalog;
 pub mod feed;
 #[cfg(feature = "filter")]
 pub mod filter;
+mod graph;
 #[cfg(feature = "html")]
 pub mod html;
+pub mod merge;
 pub mod node;
 #[cfg(feature = "retrieve")]
 pub mod retrieve;
 #[cfg(feature = "sanitise")]
 pub mod sanitise;
 pub mod seen;
+#[cfg(any(feature = "html", feature = "retrieve"))]
+mod serde_selector;
+mod validation;
 #[cfg(feature = "wasm")]
 pub mod wasm;
 
+use graph::Graph;
 use node::{Data, DataKind, Node, NodeTrait, IO};
+pub use validation::ValidationError;
 
-use crate::subscriber::websub::WebSub;
+use crate::{http::HttpClient, subscriber::websub::WebSub};
 
-#[inline]
-fn feed_io() -> Arc<IO> {
-	Arc::new(IO::new(DataKind::Feed))
+/// Environment a flow is built in, handed to every node on [`NodeTrait::init`].
+#[derive(Clone, Default, Debug)]
+pub struct Context {
+	/// Name the flow is stored under.
+	pub flow: Option<String>,
+	pub pool: Option<SqlitePool>,
+	pub http: HttpClient,
 }
 
-#[inline]
-fn feed_arr<const N: usize>() -> [Arc<IO>; N] {
-	std::array::from_fn(|_| feed_io())
+impl Context {
+	pub fn new(flow: impl Into<String>, pool: SqlitePool, http: HttpClient) -> Self {
+		Self {
+			flow: Some(flow.into()),
+			pool: Some(pool),
+			http,
+		}
+	}
 }
 
 pub struct Flow {
 	nodes: Mutex<Vec<Node>>,
+	graph: Graph,
+	concurrency: NonZeroUsize,
 
-	subscriptions: parking_lot::Mutex<Vec<WebSub>>,
+	subscriptions: parking_lot::Mutex<Vec<(WebSub, Option<Arc<IO>>)>>,
 	inputs: Box<[Arc<IO>]>,
 	outputs: Box<[Arc<IO>]>,
+	named: HashMap<String, Arc<IO>>,
+	result: Option<usize>,
 }
 
 impl Flow {
 	pub fn result(&self) -> Option<Data> {
-		self.outputs.first()?.get()
+		self.result_io()?.get()
 	}
 
-	pub fn subscriptions(&self) -> Vec<WebSub> {
-		self.subscriptions.lock().clone()
+	pub fn result_io(&self) -> Option<&Arc<IO>> {
+		self.outputs.get(self.result?)
 	}
 
-	pub fn has_subscriptions(&self) -> bool {
-		!self.subscriptions.lock().is_empty()
+	/// Data of the output named `name`, either by its port (`N3P0`) or a name given in the flow.
+	pub fn output(&self, name: &str) -> Option<Data> {
+		self.output_io(name)?.get()
 	}
-}
 
-#[async_trait]
-impl NodeTrait for Flow {
-	fn inputs(&self) -> &[Arc<IO>] {
-		&self.inputs
+	pub fn output_io(&self, name: &str) -> Option<&Arc<IO>> {
+		self.named.get(name)
 	}
 
-	fn outputs(&self) -> &[Arc<IO>] {
-		&self.outputs
+	pub fn subscriptions(&self) -> Vec<WebSub> {
+		self.subscriptions
+			.lock()
+			.iter()
+			.map(|(sub, _)| sub.clone())
+			.collect()
 	}
 
-	fn input_types(&self) -> &[DataKind] {
-		&[]
+	pub fn has_output(&self, name: &str) -> bool {
+		self.named.contains_key(name)
 	}
 
-	fn output_types(&self) -> &[DataKind] {
-		&[]
+	/// The `WebSub` input of the node subscribed to `topic`.
+	pub fn web_sub_input(&self, topic: &str) -> Option<Arc<IO>> {
+		self.subscriptions
+			.lock()
+			.iter()
+			.find(|(sub, _)| sub.topic.eq(topic))
+			.and_then(|(_, io)| io.clone())
 	}
 
-	async fn run(&self) -> anyhow::Result<()> {
-		// TODO: Run nodes in order based on input/output dependencies, run adjacent nodes concurrently.
+	pub fn has_subscriptions(&self) -> bool {
+		!self.subscriptions.lock().is_empty()
+	}
+
+	/// Type and outputs of every node, e.g. to inspect what each produced.
+	pub async fn node_outputs(&self) -> Vec<(String, Vec<(Port, Option<Data>)>)> {
+		self.nodes
+			.lock()
+			.await
+			.iter()
+			.enumerate()
+			.map(|(n, node)| {
+				let outputs = node
+					.outputs()
+					.iter()
+					.enumerate()
+					.map(|(p, io)| (Port(n, p), io.get()))
+					.collect();
+				(node.to_string(), outputs)
+			})
+			.collect()
+	}
 
-		let mut subscriptions: Option<Vec<WebSub>> = if self.subscriptions.lock().is_empty() {
+	/// Runs every dirty node, reporting how each went.
+	///
+	/// No node is started after one fails, but those already running are awaited rather than
+	/// dropped, so none is cancelled halfway through, e.g. between fetching and recording state.
+	pub async fn execute(&self) -> RunReport {
+		let mut subscriptions: Option<Vec<_>> = if self.subscriptions.lock().is_empty() {
 			Some(Vec::new())
 		} else {
 			None
 		};
 
 		let nodes = self.nodes.lock().await;
-		for node in nodes.iter() {
-			if node.is_dirty() {
-				tracing::info!("Running node: {node}");
-				node.run().await?;
-
-				let inputs = node.inputs();
-				for io in inputs.iter().filter(|i| i.is_dirty()) {
-					io.clear();
+		let previous = self.result_io().and_then(|io| io.get());
+
+		// Number of upstream nodes each node is still waiting on.
+		let mut pending: Vec<usize> = (0..self.graph.len())
+			.map(|i| self.graph.upstream(i).len())
+			.collect();
+		let mut ready: VecDeque<usize> =
+			(0..self.graph.len()).filter(|i| pending[*i] == 0).collect();
+		let mut running = FuturesUnordered::new();
+		let mut ran = Vec::new();
+		let mut report = RunReport::default();
+
+		loop {
+			while report.failed.is_none() && running.len() < self.concurrency.get() {
+				let Some(i) = ready.pop_front() else {
+					break;
+				};
+				let node = &nodes[i];
+
+				if node.is_dirty() {
+					tracing::info!("Running node: {node}");
+					running.push(async move {
+						let start = Instant::now();
+						let result = node.run().await;
+						(i, result, start.elapsed())
+					});
+				} else {
+					release(&self.graph, i, &mut pending, &mut ready);
 				}
+			}
+
+			let Some((i, result, duration)) = running.next().await else {
+				break;
+			};
+			report.nodes.push(NodeRun {
+				index: i,
+				node: nodes[i].to_string(),
+				duration,
+				error: result.as_ref().err().map(ToString::to_string),
+			});
+			match result {
+				Ok(()) => {
+					ran.push(i);
+					release(&self.graph, i, &mut pending, &mut ready);
+				}
+				Err(err) => {
+					report.failed.get_or_insert((i, err));
+				}
+			}
+		}
+
+		if report.failed.is_some() {
+			self.count_entries(&nodes, previous.as_ref(), &mut report);
+			return report;
+		}
+
+		// Inputs are only cleared once every node consuming them had the chance to run.
+		for node in ran.into_iter().map(|i| &nodes[i]) {
+			for io in node.inputs().iter().filter(|i| i.is_dirty()) {
+				io.clear();
+			}
 
-				if let Some(subscriptions) = &mut subscriptions {
-					if let Some(sub) = node.web_sub() {
-						subscriptions.push(sub);
-					}
+			if let Some(subscriptions) = &mut subscriptions {
+				if let Some(sub) = node.web_sub() {
+					let input = node
+						.inputs()
+						.iter()
+						.find(|i| matches!(i.kind(), DataKind::WebSub))
+						.cloned();
+					subscriptions.push((sub, input));
 				}
 			}
 		}
@@ -109,7 +224,66 @@ impl NodeTrait for Flow {
 			*self.subscriptions.lock() = subscriptions;
 		}
 
-		Ok(())
+		self.count_entries(&nodes, previous.as_ref(), &mut report);
+		report
+	}
+
+	/// Counts entries after a run, `previous` being the flow's result before it.
+	fn count_entries(&self, nodes: &[Node], previous: Option<&Data>, report: &mut RunReport) {
+		let count = |io: &Arc<IO>| io.get().as_ref().map_or(0, Data::entry_count);
+
+		report.entries_in = nodes
+			.iter()
+			.enumerate()
+			.filter(|(i, _)| self.graph.upstream(*i).is_empty())
+			.flat_map(|(_, node)| node.outputs().iter().map(count))
+			.sum();
+		report.entries_out = self.result_io().map_or(0, count);
+
+		let previous: HashSet<&str> = previous
+			.map(Data::entry_ids)
+			.unwrap_or_default()
+			.into_iter()
+			.collect();
+		report.entries_new = self
+			.result_io()
+			.and_then(|io| io.get())
+			.map_or(0, |result| {
+				result
+					.entry_ids()
+					.into_iter()
+					.filter(|id| !previous.contains(id))
+					.count()
+			});
+	}
+}
+
+#[async_trait]
+impl NodeTrait for Flow {
+	fn inputs(&self) -> &[Arc<IO>] {
+		&self.inputs
+	}
+
+	fn outputs(&self) -> &[Arc<IO>] {
+		&self.outputs
+	}
+
+	fn input_types(&self) -> &[DataKind] {
+		&[]
+	}
+
+	fn output_types(&self) -> &[DataKind] {
+		&[]
+	}
+
+	/// The earliest run any node wants, `None` while the flow is running.
+	fn next_run(&self) -> Option<Instant> {
+		let nodes = self.nodes.try_lock().ok()?;
+		nodes.iter().filter_map(NodeTrait::next_run).min()
+	}
+
+	async fn run(&self) -> anyhow::Result<()> {
+		self.execute().await.into_result()
 	}
 
 	fn set_input(&mut self, _index: usize, _input: Arc<IO>) {
@@ -120,11 +294,65 @@ impl NodeTrait for Flow {
 	}
 
 	fn web_sub(&self) -> Option<WebSub> {
-		self.subscriptions.lock().first().cloned()
+		self.subscriptions
+			.lock()
+			.first()
+			.map(|(sub, _)| sub.clone())
+	}
+}
+
+/// How one node did during a run.
+#[derive(Debug)]
+pub struct NodeRun {
+	pub index: usize,
+	/// Type of the node, e.g. `Feed`.
+	pub node: String,
+	pub duration: Duration,
+	pub error: Option<String>,
+}
+
+/// What happened during one run of a flow.
+#[derive(Debug, Default)]
+pub struct RunReport {
+	/// Nodes that ran, in the order they finished. Nodes that weren't dirty are left out.
+	pub nodes: Vec<NodeRun>,
+	/// The first node that failed, stopping the run.
+	pub failed: Option<(usize, anyhow::Error)>,
+	/// Entries output by the nodes without upstream nodes, e.g. fetched by `Feed` nodes.
+	pub entries_in: usize,
+	/// Entries in the flow's result.
+	pub entries_out: usize,
+	/// Entries in the flow's result that weren't in its result before the run.
+	pub entries_new: usize,
+}
+
+impl RunReport {
+	/// The error of the failed node, if any, telling which node it was.
+	pub fn into_result(self) -> anyhow::Result<()> {
+		let Some((index, err)) = self.failed else {
+			return Ok(());
+		};
+
+		let node = self
+			.nodes
+			.iter()
+			.find(|n| n.index == index)
+			.map_or("?", |n| n.node.as_str());
+		Err(err.context(format!("Node {index} ({node}) failed")))
+	}
+}
+
+/// Marks `node` as done, queueing every downstream node that has no more pending dependencies.
+fn release(graph: &Graph, node: usize, pending: &mut [usize], ready: &mut VecDeque<usize>) {
+	for next in graph.downstream(node) {
+		pending[*next] -= 1;
+		if pending[*next] == 0 {
+			ready.push_back(*next);
+		}
 	}
 }
 
-#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy)]
+#[derive(Serialize, Deserialize, JsonSchema, Eq, PartialEq, Hash, Clone, Copy, Debug)]
 pub struct Port(usize, usize);
 
 impl Display for Port {
@@ -133,14 +361,23 @@ impl Display for Port {
 	}
 }
 
-#[derive(Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, JsonSchema, Eq, PartialEq, Debug)]
 pub struct Connection(Port, Port);
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, JsonSchema, Default)]
 pub struct FlowBuilder {
 	nodes: Vec<Node>,
 	#[serde(default, skip_serializing_if = "Vec::is_empty")]
 	connections: Vec<Connection>,
+	/// Names for output ports, served at `/flow/:name/:output`.
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	outputs: BTreeMap<String, Port>,
+	/// Maximum number of nodes run at the same time. Defaults to the available parallelism.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	concurrency: Option<NonZeroUsize>,
+
+	#[serde(skip)]
+	context: Context,
 }
 
 impl FlowBuilder {
@@ -149,6 +386,11 @@ impl FlowBuilder {
 		self
 	}
 
+	pub fn context(mut self, context: Context) -> Self {
+		self.context = context;
+		self
+	}
+
 	pub fn simple(mut self) -> Self {
 		self.connections.clear();
 		for i in 0..self.nodes.len() {
@@ -161,65 +403,143 @@ impl FlowBuilder {
 		self
 	}
 
-	pub fn build(mut self) -> Flow {
-		// TODO: Improve this code once multiple-input nodes actually exist.
-		// TODO: Collect all unconnected inputs/outputs into flow inputs/outputs
-		let inputs: Box<[Arc<IO>]> = self
-			.nodes
-			.first()
-			.iter()
-			.flat_map(|n| n.input_types())
-			.map(|d| Arc::new(IO::new(*d)))
-			.collect();
+	/// Appends the nodes of `flow`, its first node fed by the first output of the last node.
+	pub fn then(mut self, flow: FlowBuilder) -> Self {
+		if self.connections.is_empty() {
+			self = self.simple();
+		}
+		let flow = if flow.connections.is_empty() {
+			flow.simple()
+		} else {
+			flow
+		};
 
-		let outputs: Box<[Arc<IO>]> = self
-			.nodes
-			.last()
-			.iter()
-			.flat_map(|n| n.output_types())
-			.map(|d| Arc::new(IO::new(*d)))
-			.collect();
+		let offset = self.nodes.len();
+		if offset > 0 && !flow.nodes.is_empty() {
+			self.connections
+				.push(Connection(Port(offset - 1, 0), Port(offset, 0)));
+		}
 
-		if !self.nodes.is_empty() {
-			if let Some(first) = self.nodes.first_mut() {
-				for (i, input) in inputs.iter().enumerate() {
-					first.set_input(i, input.clone());
-				}
+		let shift = |Port(node, port)| Port(node + offset, port);
+		self.connections.extend(
+			flow.connections
+				.into_iter()
+				.map(|Connection(from, to)| Connection(shift(from), shift(to))),
+		);
+		self.outputs.extend(
+			flow.outputs
+				.into_iter()
+				.map(|(name, port)| (name, shift(port))),
+		);
+		self.nodes.extend(flow.nodes);
+		self.concurrency = self.concurrency.or(flow.concurrency);
+
+		self
+	}
+
+	#[cfg(test)]
+	pub fn connect(mut self, from: Port, to: Port) -> Self {
+		self.connections.push(Connection(from, to));
+		self
+	}
+
+	#[cfg(test)]
+	pub fn output(mut self, name: impl Into<String>, port: Port) -> Self {
+		self.outputs.insert(name.into(), port);
+		self
+	}
+
+	pub fn build(mut self) -> Result<Flow, ValidationError> {
+		for (i, node) in self.nodes.iter_mut().enumerate() {
+			node.init(i, &self.context);
+		}
+
+		if self.connections.is_empty() {
+			self = self.simple();
+		}
+		self.validate()?;
+
+		// One IO per output port, shared by every input port connected to it.
+		let mut port_map: HashMap<Port, Arc<IO>> = HashMap::new();
+		let mut connected = HashSet::new();
+		for Connection(from, to) in &self.connections {
+			let Some(kind) = self
+				.nodes
+				.get(from.0)
+				.and_then(|n| n.output_types().get(from.1))
+				.copied()
+			else {
+				continue;
+			};
+
+			let io = port_map
+				.entry(*from)
+				.or_insert_with(|| Arc::new(IO::new(kind)))
+				.clone();
+
+			if let Some(to_n) = self.nodes.get_mut(to.0) {
+				to_n.connect(io, to.1);
+				connected.insert(*to);
 			}
-			if let Some(last) = self.nodes.last_mut() {
-				for (i, output) in outputs.iter().enumerate() {
-					last.set_output(i, output.clone());
+		}
+
+		// Every port left unconnected becomes an input/output of the flow itself.
+		let mut inputs = Vec::new();
+		let mut outputs = Vec::new();
+		for (n, node) in self.nodes.iter_mut().enumerate() {
+			for (p, kind) in node.input_types().to_vec().into_iter().enumerate() {
+				if !connected.contains(&Port(n, p)) {
+					let io = Arc::new(IO::new(kind));
+					node.set_input(p, io.clone());
+					inputs.push(io);
 				}
 			}
 
-			if self.connections.is_empty() {
-				self = self.simple();
+			for (p, kind) in node.output_types().to_vec().into_iter().enumerate() {
+				let io = port_map
+					.entry(Port(n, p))
+					.or_insert_with(|| {
+						let io = Arc::new(IO::new(kind));
+						outputs.push((Port(n, p), io.clone()));
+						io
+					})
+					.clone();
+				node.set_output(p, io);
 			}
-			let mut port_map = HashMap::new();
-			for Connection(from, to) in self.connections {
-				if let Some(from_n) = self.nodes.get_mut(from.0) {
-					if let Some(kind) = from_n.output_types().get(from.1).copied() {
-						// NOTE: If multiple outputs are connected to the same input, this will only correctly assign the last one.
-						let io = port_map.entry(from).or_insert_with(|| {
-							let io = Arc::new(IO::new(kind));
-							from_n.set_output(from.1, io.clone());
-							io
-						});
-
-						if let Some(to_n) = self.nodes.get_mut(to.0) {
-							to_n.connect(io.clone(), to.1);
-						}
-					}
-				}
+		}
+
+		// Outputs are addressable by their port (`N3P0`), and by any name given to them.
+		let mut named: HashMap<String, Arc<IO>> = outputs
+			.iter()
+			.map(|(port, io)| (port.to_string(), io.clone()))
+			.collect();
+		for (name, port) in &self.outputs {
+			if let Some(io) = port_map.get(port) {
+				named.insert(name.clone(), io.clone());
 			}
 		}
 
-		Flow {
+		// The default result is the first output of the last node that has any.
+		let result = outputs
+			.last()
+			.and_then(|(last, _)| outputs.iter().position(|(port, _)| port.0 == last.0));
+
+		let graph = Graph::new(self.nodes.len(), &self.connections);
+		let concurrency = self
+			.concurrency
+			.or_else(|| available_parallelism().ok())
+			.unwrap_or(NonZeroUsize::MIN);
+
+		Ok(Flow {
 			nodes: Mutex::new(self.nodes),
-			inputs,
-			outputs,
+			graph,
+			concurrency,
+			inputs: inputs.into(),
+			outputs: outputs.into_iter().map(|(_, io)| io).collect(),
+			named,
+			result,
 			subscriptions: parking_lot::Mutex::default(),
-		}
+		})
 	}
 }
 
@@ -227,19 +547,95 @@ impl FlowBuilder {
 mod test {
 	use std::time::Duration;
 
+	use axum::{
+		http::{header, StatusCode},
+		routing::get,
+		Router,
+	};
 	use scraper::Selector;
+	use serde_json::json;
+	use tokio::net::TcpListener;
 
 	use super::node::Field;
 	use crate::flow::{
 		feed::Feed,
 		filter::{Filter, Kind},
+		merge::Merge,
+		node::{Data, NodeTrait},
 		retrieve::Retrieve,
 		sanitise::Sanitise,
 		seen::Seen,
-		FlowBuilder,
+		FlowBuilder, Port,
 	};
 
 	#[tokio::test]
+	pub async fn ports() -> anyhow::Result<()> {
+		// Two `Seen` branches merged into one feed.
+		let flow = FlowBuilder::default()
+			.node(Seen::new())
+			.node(Seen::new())
+			.node(Merge::new(2))
+			.connect(Port(0, 0), Port(2, 0))
+			.connect(Port(1, 0), Port(2, 1))
+			.output("first", Port(0, 0))
+			.build()?;
+
+		assert_eq!(flow.inputs().len(), 2);
+		assert_eq!(flow.outputs().len(), 1);
+		assert!(flow.has_output("N2P0"));
+		assert!(!flow.has_output("N1P0"));
+
+		let feed = |id: &str| atom_syndication::Feed {
+			entries: vec![atom_syndication::Entry {
+				id: id.to_string(),
+				..Default::default()
+			}],
+			..Default::default()
+		};
+		flow.inputs()[0].accept(feed("a"))?;
+		flow.inputs()[1].accept(feed("b"))?;
+		flow.run().await?;
+
+		let Some(Data::Feed(result)) = flow.result() else {
+			panic!("Flow produced no feed");
+		};
+		assert_eq!(result.entries.len(), 2);
+
+		let Some(Data::Feed(first)) = flow.output("first") else {
+			panic!("Named output produced no feed");
+		};
+		assert_eq!(first.entries[0].id, "a");
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	pub async fn new_entries() -> anyhow::Result<()> {
+		let flow = FlowBuilder::default().node(Merge::new(1)).build()?;
+		let feed = |ids: &[&str]| atom_syndication::Feed {
+			entries: ids
+				.iter()
+				.map(|id| atom_syndication::Entry {
+					id: (*id).to_string(),
+					..Default::default()
+				})
+				.collect(),
+			..Default::default()
+		};
+
+		flow.inputs()[0].accept(feed(&["a"]))?;
+		let report = flow.execute().await;
+		assert_eq!((report.entries_out, report.entries_new), (1, 1));
+
+		flow.inputs()[0].accept(feed(&["b", "a"]))?;
+		let report = flow.execute().await;
+		assert_eq!((report.entries_out, report.entries_new), (2, 1));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	#[allow(clippy::duration_suboptimal_units)]
 	pub async fn test() -> anyhow::Result<()> {
 		let builder = FlowBuilder::default()
 			.node(Feed::new(
@@ -284,4 +680,48 @@ mod test {
 
 		Ok(())
 	}
+
+	#[tokio::test]
+	pub async fn drains_after_failure() -> anyhow::Result<()> {
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let base = format!("http://{}", listener.local_addr()?);
+		tokio::spawn(async move {
+			let router = Router::new()
+				.route("/fail", get(|| async { StatusCode::INTERNAL_SERVER_ERROR }))
+				.route(
+					"/slow",
+					get(|| async {
+						tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+						(
+							[(header::CONTENT_TYPE, "application/atom+xml")],
+							r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Slow</title><id>urn:slow</id><updated>2024-07-02T10:00:00Z</updated></feed>"#,
+						)
+					}),
+				);
+			axum::serve(listener, router).await
+		});
+
+		let flow: FlowBuilder = serde_json::from_value(json!({
+			"nodes": [
+				{ "type": "Feed", "url": format!("{base}/fail"), "ttl": 3600 },
+				{ "type": "Feed", "url": format!("{base}/slow"), "ttl": 3600 },
+				{ "type": "Merge", "count": 2 },
+			],
+			"connections": [[[0, 0], [2, 0]], [[1, 0], [2, 1]]],
+			"concurrency": 2,
+		}))?;
+		let report = flow.build()?.execute().await;
+
+		// The slow feed was awaited, but `Merge` didn't run.
+		assert_eq!(report.failed.as_ref().map(|(i, _)| *i), Some(0));
+		let mut ran: Vec<_> = report.nodes.iter().map(|n| n.index).collect();
+		ran.sort_unstable();
+		assert_eq!(ran, [0, 1]);
+		assert!(report
+			.nodes
+			.iter()
+			.any(|n| n.index == 1 && n.error.is_none()));
+
+		Ok(())
+	}
 }
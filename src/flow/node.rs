@@ -1,18 +1,22 @@
 #![allow(clippy::module_name_repetitions)]
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
 use anyhow::anyhow;
 use async_trait::async_trait;
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use derive_more::From;
 use enum_dispatch::enum_dispatch;
 use parking_lot::RwLock;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use strum::{Display, EnumDiscriminants};
 
-use crate::flow::{ai::AI, seen::Seen};
-use crate::subscriber::websub::WebSub;
+use crate::{
+	flow::{ai::AI, seen::Seen, Context},
+	subscriber::websub::WebSub,
+};
 
 #[async_trait]
 #[enum_dispatch]
@@ -34,6 +38,9 @@ pub trait NodeTrait: Sync + Send {
 	fn set_input(&mut self, index: usize, input: Arc<IO>);
 	fn set_output(&mut self, index: usize, output: Arc<IO>);
 
+	/// Called once after the node is deserialized, before any ports are connected.
+	fn init(&mut self, _index: usize, _context: &Context) {}
+
 	fn connect(&mut self, io: Arc<IO>, port: usize) {
 		if let Some(kind) = self.input_types().get(port) {
 			if io.kind.eq(kind) || DataKind::Any.eq(kind) {
@@ -45,10 +52,16 @@ pub trait NodeTrait: Sync + Send {
 	fn web_sub(&self) -> Option<WebSub> {
 		None
 	}
+
+	/// When the node wants to run on its own, e.g. to poll its source. `None` if never.
+	fn next_run(&self) -> Option<Instant> {
+		None
+	}
 }
 
-#[derive(Serialize, Deserialize, Display)]
+#[derive(Serialize, Deserialize, JsonSchema, Display)]
 #[serde(tag = "type")]
+#[allow(clippy::large_enum_variant)]
 #[enum_dispatch(NodeTrait)]
 pub enum Node {
 	AI(AI),
@@ -57,6 +70,7 @@ pub enum Node {
 	Filter(super::filter::Filter),
 	#[cfg(feature = "html")]
 	Html(super::html::Html),
+	Merge(super::merge::Merge),
 	#[cfg(feature = "retrieve")]
 	Retrieve(super::retrieve::Retrieve),
 	#[cfg(feature = "sanitise")]
@@ -103,6 +117,10 @@ impl NodeTrait for Box<dyn NodeTrait> {
 		(**self).set_output(index, output);
 	}
 
+	fn init(&mut self, index: usize, context: &Context) {
+		(**self).init(index, context);
+	}
+
 	fn connect(&mut self, io: Arc<IO>, port: usize) {
 		(**self).connect(io, port);
 	}
@@ -110,11 +128,16 @@ impl NodeTrait for Box<dyn NodeTrait> {
 	fn web_sub(&self) -> Option<WebSub> {
 		(**self).web_sub()
 	}
+
+	fn next_run(&self) -> Option<Instant> {
+		(**self).next_run()
+	}
 }
 
 #[derive(EnumDiscriminants, Serialize, Deserialize, Debug, From, Clone, PartialEq)]
 #[strum_discriminants(name(DataKind), derive(Serialize, Deserialize))]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum Data {
 	Feed(atom_syndication::Feed),
 	Entry(atom_syndication::Entry),
@@ -134,6 +157,28 @@ impl Data {
 		}
 	}
 
+	/// Number of entries, those of a feed included.
+	pub fn entry_count(&self) -> usize {
+		match self {
+			Self::Feed(feed) => feed.entries.len(),
+			Self::Entry(_) => 1,
+			Self::WebSub(_) => 0,
+			Self::Vec(data) => data.iter().map(Data::entry_count).sum(),
+			Self::Any(data) => data.entry_count(),
+		}
+	}
+
+	/// Ids of the entries, those of a feed included.
+	pub fn entry_ids(&self) -> Vec<&str> {
+		match self {
+			Self::Feed(feed) => feed.entries.iter().map(|e| e.id.as_str()).collect(),
+			Self::Entry(entry) => vec![entry.id.as_str()],
+			Self::WebSub(_) => Vec::new(),
+			Self::Vec(data) => data.iter().flat_map(Data::entry_ids).collect(),
+			Self::Any(data) => data.entry_ids(),
+		}
+	}
+
 	pub fn kind(&self) -> DataKind {
 		match self {
 			Self::Feed(_) => DataKind::Feed,
@@ -145,10 +190,18 @@ impl Data {
 	}
 }
 
+/// Identifies the data held by an [`IO`], changing whenever the data does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Revision {
+	pub version: u64,
+	pub modified: DateTime<Utc>,
+}
+
 #[derive(Default, Debug)]
 pub struct IOInner {
 	data: Option<Data>,
 	dirty: bool,
+	revision: Option<Revision>,
 }
 
 #[derive(Debug)]
@@ -174,6 +227,12 @@ impl IO {
 		if data.is_kind(self.kind) {
 			let mut inner = self.inner.write();
 			inner.dirty = inner.data.replace(data) != inner.data;
+			if inner.dirty {
+				inner.revision = Some(Revision {
+					version: inner.revision.map_or(1, |r| r.version + 1),
+					modified: Utc::now(),
+				});
+			}
 			Ok(())
 		} else {
 			Err(anyhow!("Wrong data type"))
@@ -184,6 +243,16 @@ impl IO {
 		self.inner.read().data.clone()
 	}
 
+	/// The data along with its revision, read at once.
+	pub fn snapshot(&self) -> Option<(Data, Revision)> {
+		let read = self.inner.read();
+		Some((read.data.clone()?, read.revision?))
+	}
+
+	pub fn revision(&self) -> Option<Revision> {
+		self.inner.read().revision
+	}
+
 	pub fn is_some(&self) -> bool {
 		self.inner.read().data.is_some()
 	}
@@ -207,6 +276,7 @@ impl Default for IO {
 	}
 }
 
+#[cfg(feature = "wasm")]
 pub fn collect_inputs(inputs: &Vec<Arc<IO>>) -> Option<Vec<Data>> {
 	let mut data = Vec::with_capacity(inputs.len());
 	for input in inputs {
@@ -220,7 +290,7 @@ pub fn collect_inputs(inputs: &Vec<Arc<IO>>) -> Option<Vec<Data>> {
 	Some(data)
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub enum Field {
 	Author,
 	Summary,
@@ -4,17 +4,26 @@ use anyhow::anyhow;
 use async_trait::async_trait;
 use atom_syndication::ContentBuilder;
 use futures::stream::{self, StreamExt};
+use schemars::JsonSchema;
 use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
 
-use super::node::{Data, DataKind, NodeTrait, IO};
+use super::{
+	node::{Data, DataKind, NodeTrait, IO},
+	serde_selector, Context,
+};
+use crate::http::HttpClient;
 
 /// Retrieves the full content of stub/summary entries.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct Retrieve {
 	#[serde(with = "serde_selector")]
+	#[schemars(with = "String")]
 	content: Selector,
 
+	#[serde(skip)]
+	http: HttpClient,
+
 	#[serde(skip)]
 	input: Arc<IO>,
 	#[serde(skip)]
@@ -22,9 +31,11 @@ pub struct Retrieve {
 }
 
 impl Retrieve {
+	#[cfg(test)]
 	pub fn new(content: Selector) -> Self {
 		Self {
 			content,
+			http: HttpClient::default(),
 			input: Arc::default(),
 			output: Arc::default(),
 		}
@@ -34,13 +45,14 @@ impl Retrieve {
 async fn get_content(
 	mut entry: atom_syndication::Entry,
 	selector: &Selector,
+	http: &HttpClient,
 ) -> anyhow::Result<atom_syndication::Entry> {
 	let Some(link) = entry.links().iter().find(|l| l.rel().eq("alternate")) else {
 		return Ok(entry);
 	};
 
 	tracing::info!("HTTP GET {}", link.href());
-	let content = reqwest::get(link.href()).await?.text().await?;
+	let content = http.text(http.send(http.get(link.href())).await?).await?;
 	let html = Html::parse_document(&content);
 	let content: String = html.select(selector).map(|s| s.inner_html()).collect();
 
@@ -82,7 +94,7 @@ impl NodeTrait for Retrieve {
 		let n = min(atom.entries.len(), 6); // Avoiding too high values to prevent spamming the target site.
 		let items: Vec<anyhow::Result<atom_syndication::Entry>> =
 			stream::iter(atom.entries.into_iter())
-				.map(|item| get_content(item, &self.content))
+				.map(|item| get_content(item, &self.content, &self.http))
 				.buffered(n)
 				.collect()
 				.await;
@@ -97,24 +109,8 @@ impl NodeTrait for Retrieve {
 	fn set_output(&mut self, _index: usize, output: Arc<IO>) {
 		self.output = output;
 	}
-}
-
-pub(crate) mod serde_selector {
-	use scraper::{selector::ToCss, Selector};
-	use serde::{Deserialize, Deserializer, Serializer};
-
-	pub fn serialize<S>(selector: &Selector, serializer: S) -> Result<S::Ok, S::Error>
-	where
-		S: Serializer,
-	{
-		serializer.serialize_str(&selector.to_css_string())
-	}
 
-	pub fn deserialize<'de, D>(deserializer: D) -> Result<Selector, D::Error>
-	where
-		D: Deserializer<'de>,
-	{
-		let s = String::deserialize(deserializer)?;
-		Selector::parse(&s).map_err(serde::de::Error::custom)
+	fn init(&mut self, _index: usize, context: &Context) {
+		self.http = context.http.clone();
 	}
 }
@@ -3,6 +3,7 @@ use std::{slice, sync::Arc, thread::available_parallelism};
 use anyhow::anyhow;
 use async_trait::async_trait;
 use futures::stream::{self, StreamExt};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use super::node::{Data, DataKind, Field, NodeTrait, IO};
@@ -15,7 +16,7 @@ pub fn default_ammonia() -> ammonia::Builder<'static> {
 }
 
 /// Removes unnecessary elements/attributes from entry html.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct Sanitise {
 	field: Field,
 
@@ -29,6 +30,7 @@ pub struct Sanitise {
 }
 
 impl Sanitise {
+	#[cfg(test)]
 	pub fn new(field: Field) -> Self {
 		Self {
 			field,
@@ -1,18 +1,31 @@
-use std::{collections::HashSet, slice, sync::Arc};
+use std::{collections::HashSet, slice, sync::Arc, time::Duration};
 
 use anyhow::anyhow;
 use async_trait::async_trait;
+use chrono::Utc;
 use parking_lot::Mutex;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationSeconds};
+use sqlx::SqlitePool;
 
-use super::node::{Data, DataKind, NodeTrait, IO};
+use super::{
+	node::{Data, DataKind, NodeTrait, IO},
+	Context,
+};
 
 /// Filters out already processed entries.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct Seen {
 	#[serde(default)]
 	store: Store,
 
+	#[serde(skip)]
+	memory: Mutex<HashSet<String>>,
+	/// Where the `Database` store keeps its ids, set when the flow is built.
+	#[serde(skip)]
+	database: Option<(SqlitePool, String, i64)>,
+
 	#[serde(skip)]
 	input: Arc<IO>,
 	#[serde(skip)]
@@ -20,14 +33,104 @@ pub struct Seen {
 }
 
 impl Seen {
+	#[cfg(test)]
 	pub fn new() -> Self {
+		Self::with_store(Store::default())
+	}
+
+	#[cfg(test)]
+	pub fn database(retention: Retention) -> Self {
+		Self::with_store(Store::Database(retention))
+	}
+
+	#[cfg(test)]
+	fn with_store(store: Store) -> Self {
 		Self {
-			store: Store::default(),
+			store,
+			memory: Mutex::default(),
+			database: None,
 
 			output: Arc::default(),
 			input: Arc::default(),
 		}
 	}
+
+	/// Keeps only the entries not seen before, recording the rest as seen.
+	async fn retain_unseen(
+		&self,
+		(pool, flow, node): &(SqlitePool, String, i64),
+		retention: &Retention,
+		entries: &mut Vec<atom_syndication::Entry>,
+	) -> anyhow::Result<()> {
+		let now = Utc::now();
+		let mut tx = pool.begin().await?;
+
+		let mut unseen = HashSet::new();
+		for entry in entries.iter() {
+			// Entries still present upstream are touched, so they never expire while they are.
+			let touched = sqlx::query!(
+				"UPDATE seen SET last_seen = ? WHERE flow = ? AND node = ? AND id = ?",
+				now,
+				flow,
+				node,
+				entry.id
+			)
+			.execute(&mut *tx)
+			.await?
+			.rows_affected();
+
+			if touched == 0 {
+				sqlx::query!(
+					"INSERT INTO seen (flow, node, id, last_seen) VALUES (?, ?, ?, ?)",
+					flow,
+					node,
+					entry.id,
+					now
+				)
+				.execute(&mut *tx)
+				.await?;
+
+				unseen.insert(entry.id.clone());
+			}
+		}
+
+		if let Some(max_age) = retention.max_age {
+			let cutoff = now - max_age;
+			sqlx::query!(
+				"DELETE FROM seen WHERE flow = ? AND node = ? AND last_seen < ?",
+				flow,
+				node,
+				cutoff
+			)
+			.execute(&mut *tx)
+			.await?;
+		}
+
+		if let Some(max_count) = retention.max_count {
+			sqlx::query!(
+				r#"
+				DELETE FROM seen
+				WHERE flow = ?1 AND node = ?2 AND id NOT IN (
+					SELECT id
+					FROM seen
+					WHERE flow = ?1 AND node = ?2
+					ORDER BY last_seen DESC, rowid DESC
+					LIMIT ?3
+				)
+				"#,
+				flow,
+				node,
+				max_count
+			)
+			.execute(&mut *tx)
+			.await?;
+		}
+
+		tx.commit().await?;
+
+		entries.retain(|e| unseen.contains(&e.id));
+		Ok(())
+	}
 }
 
 #[async_trait]
@@ -37,7 +140,7 @@ impl NodeTrait for Seen {
 	}
 
 	fn outputs(&self) -> &[Arc<IO>] {
-		slice::from_ref(&self.input)
+		slice::from_ref(&self.output)
 	}
 
 	fn input_types(&self) -> &[DataKind] {
@@ -48,14 +151,18 @@ impl NodeTrait for Seen {
 		&[DataKind::Feed]
 	}
 
-	#[tracing::instrument(name = "seen_node")]
+	#[tracing::instrument(name = "seen_node", skip(self))]
 	async fn run(&self) -> anyhow::Result<()> {
 		let Some(Data::Feed(mut atom)) = self.input.get() else {
 			return Err(anyhow!("Input data not available"));
 		};
 
-		if let Store::Internal(seen) = &self.store {
-			let mut seen = seen.lock();
+		if let (Store::Database(retention), Some(database)) = (&self.store, &self.database) {
+			self.retain_unseen(database, retention, &mut atom.entries)
+				.await?;
+		} else {
+			// Without a database (e.g. a flow that isn't stored), ids are only kept in memory.
+			let mut seen = self.memory.lock();
 
 			// seen.retain(|id| atom.entries.iter().any(|i| i.id.eq(id)));
 			atom.entries.retain(|item| seen.insert(item.id.clone()));
@@ -73,16 +180,100 @@ impl NodeTrait for Seen {
 	fn set_output(&mut self, _index: usize, output: Arc<IO>) {
 		self.output = output;
 	}
+
+	fn init(&mut self, index: usize, context: &Context) {
+		if let (Store::Database(..), Some(flow), Some(pool)) =
+			(&self.store, &context.flow, &context.pool)
+		{
+			self.database = Some((
+				pool.clone(),
+				flow.clone(),
+				i64::try_from(index).unwrap_or_default(),
+			));
+		}
+	}
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub enum Store {
+	/// Keeps seen ids in memory, until the flow is reloaded.
+	#[default]
+	Internal,
+	/// Keeps seen ids in the `seen` table.
+	Database(Retention),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-enum Store {
-	Internal(#[serde(skip)] Mutex<HashSet<String>>),
-	// External,
+/// How long the `Database` store remembers ids of entries no longer present in the input.
+///
+/// Should cover the time an entry stays in the upstream feed, or it will be seen as new again.
+#[serde_as]
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Retention {
+	/// Seconds.
+	#[serde_as(as = "Option<DurationSeconds>")]
+	#[schemars(with = "Option<u64>")]
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub max_age: Option<Duration>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub max_count: Option<u32>,
 }
 
-impl Default for Store {
-	fn default() -> Self {
-		Self::Internal(Mutex::default())
+#[cfg(test)]
+mod test {
+
+	use super::{Retention, Seen};
+	use crate::{
+		flow::{
+			node::{Data, NodeTrait},
+			Context,
+		},
+		http::HttpClient,
+	};
+
+	fn feed(ids: &[&str]) -> atom_syndication::Feed {
+		atom_syndication::Feed {
+			entries: ids
+				.iter()
+				.map(|id| atom_syndication::Entry {
+					id: (*id).to_string(),
+					..Default::default()
+				})
+				.collect(),
+			..Default::default()
+		}
+	}
+
+	async fn run(node: &Seen, ids: &[&str]) -> anyhow::Result<Vec<String>> {
+		node.inputs()[0].accept(feed(ids))?;
+		node.run().await?;
+
+		let Some(Data::Feed(feed)) = node.outputs()[0].get() else {
+			return Ok(Vec::new());
+		};
+		Ok(feed.entries.into_iter().map(|e| e.id).collect())
+	}
+
+	#[tokio::test]
+	pub async fn database() -> anyhow::Result<()> {
+		let pool = crate::test::pool().await?;
+		let context = Context::new("test", pool, HttpClient::default());
+
+		let retention = || Retention {
+			max_count: Some(2),
+			..Default::default()
+		};
+
+		let mut node = Seen::database(retention());
+		node.init(0, &context);
+		assert_eq!(run(&node, &["a", "b"]).await?, ["a", "b"]);
+		assert_eq!(run(&node, &["a", "b", "c"]).await?, ["c"]);
+
+		// A rebuilt node remembers the ids of the previous one, up to `max_count`.
+		let mut node = Seen::database(retention());
+		node.init(0, &context);
+		assert_eq!(run(&node, &["c", "d"]).await?, ["d"]);
+		assert_eq!(run(&node, &["a"]).await?, ["a"]);
+
+		Ok(())
 	}
 }
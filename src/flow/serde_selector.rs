@@ -0,0 +1,44 @@
+use scraper::{selector::ToCss, Selector};
+use serde::{Deserialize, Deserializer, Serializer};
+
+pub fn serialize<S>(selector: &Selector, serializer: S) -> Result<S::Ok, S::Error>
+where
+	S: Serializer,
+{
+	serializer.serialize_str(&selector.to_css_string())
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Selector, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let s = String::deserialize(deserializer)?;
+	Selector::parse(&s).map_err(serde::de::Error::custom)
+}
+
+/// For optional selectors, use with `#[serde(default, with = "serde_selector::option")]`.
+#[cfg(feature = "html")]
+pub mod option {
+	use scraper::{selector::ToCss, Selector};
+	use serde::{Deserialize, Deserializer, Serializer};
+
+	#[allow(clippy::ref_option)]
+	pub fn serialize<S>(selector: &Option<Selector>, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+	{
+		match selector {
+			Some(selector) => serializer.serialize_some(&selector.to_css_string()),
+			None => serializer.serialize_none(),
+		}
+	}
+
+	pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Selector>, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		Option::<String>::deserialize(deserializer)?
+			.map(|s| Selector::parse(&s).map_err(serde::de::Error::custom))
+			.transpose()
+	}
+}
@@ -0,0 +1,344 @@
+use std::{
+	collections::HashSet,
+	fmt::{Display, Formatter},
+};
+
+use serde::Serialize;
+
+use super::{
+	graph::Graph,
+	merge::MAX_INPUTS,
+	node::{DataKind, Node},
+	Connection, FlowBuilder, Port,
+};
+use crate::flow::node::NodeTrait;
+
+/// Output names taken by other routes under `/flow/:name/`.
+const RESERVED_OUTPUTS: &[&str] = &["sse"];
+
+/// Everything wrong with a flow, found while building it.
+#[derive(Serialize, Debug)]
+pub struct ValidationError {
+	pub problems: Vec<Problem>,
+}
+
+impl Display for ValidationError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		for (i, problem) in self.problems.iter().enumerate() {
+			if i > 0 {
+				f.write_str("; ")?;
+			}
+			problem.fmt(f)?;
+		}
+
+		Ok(())
+	}
+}
+
+impl std::error::Error for ValidationError {}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Problem {
+	pub node: usize,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub port: Option<usize>,
+	#[serde(flatten)]
+	pub kind: ProblemKind,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "problem", rename_all = "snake_case")]
+pub enum ProblemKind {
+	/// A connection refers to a node that does not exist.
+	UnknownNode,
+	/// A connection refers to an input port the node does not have.
+	UnknownInput,
+	/// A connection or named output refers to an output port the node does not have.
+	UnknownOutput,
+	/// The input is connected to an output of a different [`DataKind`].
+	KindMismatch {
+		from: Port,
+		output: DataKind,
+		input: DataKind,
+	},
+	/// The input is connected more than once.
+	DuplicateInput { from: Port },
+	/// The node is not connected to any other node.
+	Dangling,
+	/// The node has no inputs, e.g. a `Merge` of no feeds, so it never produces anything.
+	NoInputs,
+	/// The node would have more inputs than allowed, e.g. a `Merge` of too many feeds.
+	TooManyInputs { max: usize },
+	/// The node is part of a cycle.
+	Cycle,
+	/// The output is named like a route of the flow, see [`RESERVED_OUTPUTS`].
+	ReservedName { name: String },
+}
+
+impl Display for Problem {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		if let Some(port) = self.port {
+			write!(f, "{}: ", Port(self.node, port))?;
+		} else {
+			write!(f, "N{}: ", self.node)?;
+		}
+
+		match &self.kind {
+			ProblemKind::UnknownNode => f.write_str("unknown node"),
+			ProblemKind::UnknownInput => f.write_str("unknown input port"),
+			ProblemKind::UnknownOutput => f.write_str("unknown output port"),
+			ProblemKind::KindMismatch {
+				from,
+				output,
+				input,
+			} => write!(f, "expects {input:?}, but {from} outputs {output:?}"),
+			ProblemKind::DuplicateInput { from } => write!(f, "already connected, also to {from}"),
+			ProblemKind::Dangling => f.write_str("not connected to any other node"),
+			ProblemKind::NoInputs => f.write_str("has no inputs"),
+			ProblemKind::TooManyInputs { max } => write!(f, "has more than {max} inputs"),
+			ProblemKind::Cycle => f.write_str("part of a cycle"),
+			ProblemKind::ReservedName { name } => write!(f, "output name `{name}` is reserved"),
+		}
+	}
+}
+
+impl Problem {
+	fn new(node: usize, port: Option<usize>, kind: ProblemKind) -> Self {
+		Self { node, port, kind }
+	}
+}
+
+impl FlowBuilder {
+	/// Checks connections and named outputs against the ports of each node.
+	/// Nodes must already be initialised.
+	pub(super) fn validate(&self) -> Result<(), ValidationError> {
+		let mut problems = Vec::new();
+
+		let output_kind = |port: &Port, problems: &mut Vec<Problem>| -> Option<DataKind> {
+			let Some(node) = self.nodes.get(port.0) else {
+				problems.push(Problem::new(port.0, None, ProblemKind::UnknownNode));
+				return None;
+			};
+			let kind = node.output_types().get(port.1).copied();
+			if kind.is_none() {
+				problems.push(Problem::new(
+					port.0,
+					Some(port.1),
+					ProblemKind::UnknownOutput,
+				));
+			}
+			kind
+		};
+
+		let mut connected = HashSet::new();
+		let mut wired = Vec::<Connection>::new();
+		for Connection(from, to) in &self.connections {
+			connected.insert(from.0);
+			connected.insert(to.0);
+
+			let output = output_kind(from, &mut problems);
+
+			let input = if let Some(node) = self.nodes.get(to.0) {
+				let kind = node.input_types().get(to.1).copied();
+				if kind.is_none() {
+					problems.push(Problem::new(to.0, Some(to.1), ProblemKind::UnknownInput));
+				}
+				kind
+			} else {
+				problems.push(Problem::new(to.0, None, ProblemKind::UnknownNode));
+				None
+			};
+
+			let (Some(output), Some(input)) = (output, input) else {
+				continue;
+			};
+
+			if output != input && input != DataKind::Any {
+				problems.push(Problem::new(
+					to.0,
+					Some(to.1),
+					ProblemKind::KindMismatch {
+						from: *from,
+						output,
+						input,
+					},
+				));
+			}
+
+			if let Some(Connection(first, _)) = wired.iter().find(|c| c.1.eq(to)) {
+				if first.ne(from) {
+					problems.push(Problem::new(
+						to.0,
+						Some(to.1),
+						ProblemKind::DuplicateInput { from: *from },
+					));
+				}
+			} else {
+				wired.push(Connection(*from, *to));
+			}
+		}
+
+		for (name, port) in &self.outputs {
+			output_kind(port, &mut problems);
+			if RESERVED_OUTPUTS.contains(&name.as_str()) {
+				problems.push(Problem::new(
+					port.0,
+					Some(port.1),
+					ProblemKind::ReservedName { name: name.clone() },
+				));
+			}
+		}
+
+		for (i, node) in self.nodes.iter().enumerate() {
+			if let Node::Merge(merge) = node {
+				if merge.count() > MAX_INPUTS {
+					problems.push(Problem::new(
+						i,
+						None,
+						ProblemKind::TooManyInputs { max: MAX_INPUTS },
+					));
+					continue;
+				}
+			}
+			if node.input_types().is_empty() {
+				problems.push(Problem::new(i, None, ProblemKind::NoInputs));
+			}
+		}
+
+		if self.nodes.len() > 1 {
+			for node in (0..self.nodes.len()).filter(|n| !connected.contains(n)) {
+				problems.push(Problem::new(node, None, ProblemKind::Dangling));
+			}
+		}
+
+		let graph = Graph::new(self.nodes.len(), &self.connections);
+		for node in graph.cyclic() {
+			problems.push(Problem::new(node, None, ProblemKind::Cycle));
+		}
+
+		if problems.is_empty() {
+			Ok(())
+		} else {
+			Err(ValidationError { problems })
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::time::Duration;
+
+	use super::{Problem, ProblemKind, MAX_INPUTS};
+	use crate::flow::{feed::Feed, merge::Merge, node::DataKind, seen::Seen, FlowBuilder, Port};
+
+	#[test]
+	pub fn problems() {
+		let err = FlowBuilder::default()
+			.node(Seen::new())
+			.node(Seen::new())
+			.node(Merge::new(2))
+			.node(Seen::new())
+			.connect(Port(0, 0), Port(2, 0))
+			.connect(Port(1, 0), Port(2, 0))
+			.connect(Port(1, 1), Port(2, 1))
+			.connect(Port(0, 0), Port(5, 0))
+			.build()
+			.err()
+			.expect("flow should not be valid");
+
+		assert_eq!(
+			err.problems,
+			[
+				Problem::new(2, Some(0), ProblemKind::DuplicateInput { from: Port(1, 0) }),
+				Problem::new(1, Some(1), ProblemKind::UnknownOutput),
+				Problem::new(5, None, ProblemKind::UnknownNode),
+				Problem::new(3, None, ProblemKind::Dangling),
+			]
+		);
+	}
+
+	#[test]
+	pub fn kinds_and_cycles() {
+		let err = FlowBuilder::default()
+			.node(Seen::new())
+			.node(Feed::new(
+				"http://localhost/feed".parse().unwrap(),
+				Duration::from_hours(1),
+			))
+			.node(Seen::new())
+			.connect(Port(0, 0), Port(1, 0))
+			.connect(Port(1, 0), Port(2, 0))
+			.connect(Port(2, 0), Port(0, 0))
+			.build()
+			.err()
+			.expect("flow should not be valid");
+
+		assert_eq!(
+			err.problems,
+			[
+				Problem::new(
+					1,
+					Some(0),
+					ProblemKind::KindMismatch {
+						from: Port(0, 0),
+						output: DataKind::Feed,
+						input: DataKind::WebSub,
+					}
+				),
+				Problem::new(0, None, ProblemKind::Cycle),
+				Problem::new(1, None, ProblemKind::Cycle),
+				Problem::new(2, None, ProblemKind::Cycle),
+			]
+		);
+	}
+
+	#[test]
+	pub fn reserved_output_names() {
+		let err = FlowBuilder::default()
+			.node(Seen::new())
+			.output("sse", Port(0, 0))
+			.build()
+			.err()
+			.expect("flow should not be valid");
+
+		assert_eq!(
+			err.problems,
+			[Problem::new(
+				0,
+				Some(0),
+				ProblemKind::ReservedName {
+					name: "sse".to_string()
+				}
+			)]
+		);
+	}
+
+	#[test]
+	pub fn no_inputs() {
+		let err = FlowBuilder::default()
+			.node(Merge::new(0))
+			.build()
+			.err()
+			.expect("flow should not be valid");
+
+		assert_eq!(err.problems, [Problem::new(0, None, ProblemKind::NoInputs)]);
+	}
+
+	#[test]
+	pub fn too_many_inputs() {
+		let err = FlowBuilder::default()
+			.node(Merge::new(1_000_000_000))
+			.build()
+			.err()
+			.expect("flow should not be valid");
+
+		assert_eq!(
+			err.problems,
+			[Problem::new(
+				0,
+				None,
+				ProblemKind::TooManyInputs { max: MAX_INPUTS }
+			)]
+		);
+	}
+}
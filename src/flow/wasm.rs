@@ -37,8 +37,8 @@ impl Wasm {
 		let mut linker = Linker::new(&engine);
 		preview1::add_to_linker_async(&mut linker, |t| t)?;
 
-		let stdin = MyInputPipe::new();
-		let stdout = MyOutputPipe::new();
+		let stdin = MyInputPipe::default();
+		let stdout = MyOutputPipe::default();
 
 		let wasi_ctx = WasiCtxBuilder::new()
 			.stdin(stdin.clone())
@@ -63,8 +63,8 @@ impl Wasm {
 			stdin,
 			stdout,
 
-			inputs: Vec::new(),
-			outputs: Vec::new(),
+			inputs: inputs.iter().map(|k| Arc::new(IO::new(*k))).collect(),
+			outputs: outputs.iter().map(|k| Arc::new(IO::new(*k))).collect(),
 
 			input_types: inputs.into(),
 			output_types: outputs.into(),
@@ -120,10 +120,14 @@ impl NodeTrait for Wasm {
 	}
 
 	fn set_input(&mut self, index: usize, input: Arc<IO>) {
-		self.inputs.insert(index, input);
+		if let Some(slot) = self.inputs.get_mut(index) {
+			*slot = input;
+		}
 	}
 	fn set_output(&mut self, index: usize, output: Arc<IO>) {
-		self.outputs.insert(index, output);
+		if let Some(slot) = self.outputs.get_mut(index) {
+			*slot = output;
+		}
 	}
 }
 
@@ -137,31 +141,17 @@ mod pipe {
 		HostInputStream, HostOutputStream, StdinStream, StdoutStream, StreamError, Subscribe,
 	};
 
-	#[derive(Debug, Clone)]
+	#[derive(Debug, Clone, Default)]
 	pub struct MyInputPipe {
 		pub buffer: Arc<Mutex<BytesMut>>,
 	}
 
-	#[derive(Debug, Clone)]
+	#[derive(Debug, Clone, Default)]
 	pub struct MyOutputPipe {
 		pub buffer: Arc<Mutex<BytesMut>>,
 	}
 
-	impl MyInputPipe {
-		pub fn new() -> Self {
-			Self {
-				buffer: Arc::new(Mutex::new(BytesMut::new())),
-			}
-		}
-	}
-
 	impl MyOutputPipe {
-		pub fn new() -> Self {
-			Self {
-				buffer: Arc::new(Mutex::new(BytesMut::new())),
-			}
-		}
-
 		pub fn clear(&self) {
 			self.buffer.lock().clear();
 		}
@@ -230,7 +220,6 @@ mod pipe {
 
 #[cfg(test)]
 mod test {
-	use super::*;
 	#[tokio::test]
 	pub async fn wasm() -> anyhow::Result<()> {
 		// let flow = Dummy::<Feed>::default()
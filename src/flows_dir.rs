@@ -0,0 +1,269 @@
+//! Flows loaded from the `flows_dir` directory, so they can be kept in git.
+//!
+//! Each `<name>.json` or `<name>.toml` file holds a flow. Loaded flows are stored in `flows` with
+//! their file, which makes them read-only in the API, and are removed along with their file.
+
+use std::{
+	collections::{BTreeMap, HashMap},
+	ffi::OsStr,
+	path::{Path, PathBuf},
+	time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, bail};
+use serde::de::DeserializeOwned;
+use sqlx::SqlitePool;
+use tokio::time::interval;
+
+use crate::{
+	app::AppState,
+	flow::{Context, FlowBuilder},
+};
+
+/// How often the directory is checked for changed files.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Modification time and size of a file, to notice it changed.
+type Stamp = (Option<SystemTime>, u64);
+
+pub struct FlowsDir {
+	dir: PathBuf,
+	stamps: HashMap<PathBuf, Stamp>,
+}
+
+impl FlowsDir {
+	pub fn new(dir: PathBuf) -> Self {
+		Self {
+			dir,
+			stamps: HashMap::new(),
+		}
+	}
+
+	/// Syncs flows with the directory whenever its files change. Polling rather than file
+	/// system events also picks up checkouts swapping a symlinked directory.
+	pub async fn watch(mut self, state: AppState) {
+		let mut interval = interval(POLL_INTERVAL);
+		interval.tick().await;
+
+		loop {
+			interval.tick().await;
+			self.sync(&state).await;
+		}
+	}
+
+	/// Loads new and changed files, and removes flows whose file is gone. A file that fails to
+	/// load is logged, the flow keeps running as it was until the file is fixed. Files named
+	/// like a flow stored through the API are refused, so they never replace nor remove it.
+	pub async fn sync(&mut self, state: &AppState) {
+		if let Err(err) = self.try_sync(state).await {
+			tracing::error!("Syncing flows from `{}` failed: {err}", self.dir.display());
+		}
+	}
+
+	async fn try_sync(&mut self, state: &AppState) -> anyhow::Result<()> {
+		let files = files(&self.dir).await?;
+
+		for (name, path) in &files {
+			let metadata = match tokio::fs::metadata(path).await {
+				Ok(metadata) => metadata,
+				Err(err) => {
+					tracing::error!("Failed reading `{}`: {err}", path.display());
+					continue;
+				}
+			};
+			let stamp = (metadata.modified().ok(), metadata.len());
+			if self.stamps.get(path) == Some(&stamp) {
+				continue;
+			}
+			self.stamps.insert(path.clone(), stamp);
+
+			if let Err(err) = load(state, name, path).await {
+				tracing::error!("Failed loading `{}`: {err:#}", path.display());
+			}
+		}
+		self.stamps
+			.retain(|path, _| files.values().any(|p| p == path));
+
+		let loaded =
+			sqlx::query!(r#"SELECT name, file as "file!" FROM flows WHERE file IS NOT NULL"#)
+				.fetch_all(&state.pool)
+				.await?;
+		for record in loaded {
+			if files.contains_key(&record.name) {
+				continue;
+			}
+			match state.remove_flow(&record.name).await {
+				Ok(()) => {
+					tracing::info!("Removed `{}` flow, `{}` is gone", record.name, record.file);
+				}
+				Err(err) => tracing::error!("Failed removing `{}` flow: {err}", record.name),
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Makes flows loaded from a directory that isn't configured anymore writable.
+pub async fn release(pool: &SqlitePool) -> anyhow::Result<()> {
+	let released = sqlx::query!("UPDATE flows SET file = NULL WHERE file IS NOT NULL")
+		.execute(pool)
+		.await?
+		.rows_affected();
+	if released > 0 {
+		tracing::info!("Flows directory unset, {released} flows loaded from it are writable now");
+	}
+
+	Ok(())
+}
+
+/// Flow files by flow name.
+async fn files(dir: &Path) -> anyhow::Result<BTreeMap<String, PathBuf>> {
+	let mut paths = Vec::new();
+	let mut entries = tokio::fs::read_dir(dir).await?;
+	while let Some(entry) = entries.next_entry().await? {
+		let path = entry.path();
+		if matches!(
+			path.extension().and_then(OsStr::to_str),
+			Some("json" | "toml")
+		) && tokio::fs::metadata(&path).await?.is_file()
+		{
+			paths.push(path);
+		}
+	}
+	paths.sort();
+
+	let mut files: BTreeMap<String, PathBuf> = BTreeMap::new();
+	for path in paths {
+		let Some(name) = path.file_stem().and_then(OsStr::to_str) else {
+			continue;
+		};
+
+		if let Some(other) = files.get(name) {
+			tracing::error!(
+				"Ignoring `{}`, `{}` already defines `{name}` flow",
+				path.display(),
+				other.display()
+			);
+			continue;
+		}
+		files.insert(name.to_string(), path);
+	}
+
+	Ok(files)
+}
+
+/// Reads a `.json` or `.toml` file, a flow here or in the CLI, or flows to import.
+pub(crate) async fn parse<T: DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+	let content = tokio::fs::read_to_string(path).await?;
+
+	match path.extension().and_then(OsStr::to_str) {
+		Some("toml") => Ok(toml::from_str(&content)?),
+		Some("json") => Ok(serde_json::from_str(&content)?),
+		_ => Err(anyhow!("Unknown flow file type")),
+	}
+}
+
+async fn load(state: &AppState, name: &str, path: &Path) -> anyhow::Result<()> {
+	let flow: FlowBuilder = parse(path).await?;
+	let json = serde_json::to_string(&flow)?;
+	let flow = flow
+		.context(Context::new(name, state.pool.clone(), state.http.clone()))
+		.build()?;
+	let file = path.to_string_lossy();
+
+	let stored = sqlx::query!("SELECT content, file FROM flows WHERE name = ?", name)
+		.fetch_optional(&state.pool)
+		.await?;
+	match stored {
+		// Loaded already, before a restart or a touch of the file.
+		Some(stored)
+			if stored.content == json
+				&& stored.file.as_deref() == Some(&file)
+				&& state.flows.lock().await.contains_key(name) =>
+		{
+			return Ok(());
+		}
+		Some(stored) if stored.file.is_none() => {
+			bail!("`{name}` flow is stored through the API, rename the file or delete that flow");
+		}
+		_ => {}
+	}
+
+	sqlx::query!(
+		r#"
+		INSERT INTO flows (name, content, file) VALUES (?, ?, ?)
+		ON CONFLICT (name) DO UPDATE SET content = excluded.content, file = excluded.file
+		"#,
+		name,
+		json,
+		file
+	)
+	.execute(&state.pool)
+	.await?;
+
+	state.install_flow(name, flow).await?;
+	tracing::info!("Loaded `{name}` flow from `{file}`");
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod test {
+	use std::collections::HashMap;
+
+	use super::FlowsDir;
+	use crate::{app::AppState, http::HttpClient};
+
+	#[tokio::test]
+	pub async fn sync() -> anyhow::Result<()> {
+		let pool = crate::test::pool().await?;
+		sqlx::query("INSERT INTO flows (name, content) VALUES ('blog', '{}'), ('api', '{}')")
+			.execute(&pool)
+			.await?;
+		let state = AppState::new(HashMap::new(), pool.clone(), HttpClient::default());
+
+		let dir = std::env::temp_dir().join(format!("rssflow-flows-{}", std::process::id()));
+		std::fs::create_dir_all(&dir)?;
+		std::fs::write(
+			dir.join("news.json"),
+			r#"{ "nodes": [{ "type": "Feed", "url": "https://example.com/news.xml", "ttl": 60 }] }"#,
+		)?;
+		std::fs::write(
+			dir.join("blog.toml"),
+			"[[nodes]]\ntype = \"Feed\"\nurl = \"https://example.com/blog.xml\"\nttl = 60\n",
+		)?;
+		std::fs::write(
+			dir.join("broken.json"),
+			r#"{ "nodes": [{ "type": "Feed" }] }"#,
+		)?;
+		std::fs::write(dir.join("notes.txt"), "")?;
+
+		let mut flows_dir = FlowsDir::new(dir.clone());
+		flows_dir.sync(&state).await;
+
+		let loaded: Vec<_> = state.flows.lock().await.keys().cloned().collect();
+		assert_eq!(loaded, ["news"]);
+
+		// `blog.toml` is refused, the `blog` flow stored through the API stays as it was.
+		let files: Vec<(String, String, bool)> =
+			sqlx::query_as("SELECT name, content, file IS NOT NULL FROM flows ORDER BY name")
+				.fetch_all(&pool)
+				.await?;
+		assert_eq!(files[0], ("api".to_string(), "{}".to_string(), false));
+		assert_eq!(files[1], ("blog".to_string(), "{}".to_string(), false));
+		assert_eq!((files[2].0.as_str(), files[2].2), ("news", true));
+
+		std::fs::remove_file(dir.join("news.json"))?;
+		std::fs::remove_file(dir.join("blog.toml"))?;
+		flows_dir.sync(&state).await;
+		assert!(!state.flows.lock().await.contains_key("news"));
+		let names: Vec<String> = sqlx::query_scalar("SELECT name FROM flows ORDER BY name")
+			.fetch_all(&pool)
+			.await?;
+		assert_eq!(names, ["api", "blog"]);
+
+		std::fs::remove_dir_all(&dir)?;
+		Ok(())
+	}
+}
@@ -0,0 +1,189 @@
+//! Run history of flows, kept in the `flow_runs` table.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use strum::IntoStaticStr;
+
+use crate::flow::RunReport;
+
+/// Runs kept per flow, older ones are deleted.
+const KEEP: i64 = 1000;
+
+/// What caused a flow to run.
+#[derive(sqlx::Type, Serialize, IntoStaticStr, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Trigger {
+	/// A request to `/flow/:name`.
+	Http,
+	/// A `WebSub` push to one of its nodes.
+	WebSub,
+	/// Polling by the scheduler.
+	Schedule,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Run {
+	pub id: i64,
+	pub trigger: Trigger,
+	pub started: DateTime<Utc>,
+	pub finished: DateTime<Utc>,
+	/// Index of the node that failed.
+	pub failed_node: Option<i64>,
+	pub error: Option<String>,
+	pub entries_in: i64,
+	pub entries_out: i64,
+}
+
+/// Records a run, unless no node ran because none was dirty.
+pub async fn record(
+	pool: &SqlitePool,
+	flow: &str,
+	trigger: Trigger,
+	started: DateTime<Utc>,
+	report: &RunReport,
+) -> anyhow::Result<()> {
+	if report.nodes.is_empty() {
+		return Ok(());
+	}
+
+	let finished = Utc::now();
+	let (failed_node, error) = match &report.failed {
+		Some((node, err)) => (Some(i64::try_from(*node)?), Some(format!("{err:#}"))),
+		None => (None, None),
+	};
+	let entries_in = i64::try_from(report.entries_in)?;
+	let entries_out = i64::try_from(report.entries_out)?;
+
+	sqlx::query!(
+		r#"
+		INSERT INTO flow_runs (flow, trigger, started, finished, failed_node, error, entries_in, entries_out)
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+		"#,
+		flow,
+		trigger as _,
+		started,
+		finished,
+		failed_node,
+		error,
+		entries_in,
+		entries_out
+	)
+	.execute(pool)
+	.await?;
+
+	sqlx::query!(
+		r#"
+		DELETE FROM flow_runs
+		WHERE flow = ?1 AND id <= (
+			SELECT id FROM flow_runs WHERE flow = ?1 ORDER BY id DESC LIMIT 1 OFFSET ?2
+		)
+		"#,
+		flow,
+		KEEP
+	)
+	.execute(pool)
+	.await?;
+
+	Ok(())
+}
+
+/// Runs of a flow, newest first, older than the run `before` if given.
+pub async fn runs(
+	pool: &SqlitePool,
+	flow: &str,
+	before: Option<i64>,
+	limit: u32,
+) -> anyhow::Result<Vec<Run>> {
+	let before = before.unwrap_or(i64::MAX);
+
+	Ok(sqlx::query_as!(
+		Run,
+		r#"
+		SELECT
+			id as "id!",
+			trigger as "trigger: Trigger",
+			started as "started: DateTime<Utc>",
+			finished as "finished: DateTime<Utc>",
+			failed_node,
+			error,
+			entries_in,
+			entries_out
+		FROM flow_runs
+		WHERE flow = ? AND id < ?
+		ORDER BY id DESC
+		LIMIT ?
+		"#,
+		flow,
+		before,
+		limit
+	)
+	.fetch_all(pool)
+	.await?)
+}
+
+#[cfg(test)]
+mod test {
+	use anyhow::anyhow;
+	use chrono::Utc;
+
+	use super::{record, runs, Trigger};
+	use crate::flow::{NodeRun, RunReport};
+
+	#[tokio::test]
+	pub async fn history() -> anyhow::Result<()> {
+		let pool = crate::test::pool().await?;
+
+		let ran = |error: Option<&str>| RunReport {
+			nodes: vec![NodeRun {
+				index: 1,
+				node: "Retrieve".to_string(),
+				duration: std::time::Duration::ZERO,
+				error: error.map(String::from),
+			}],
+			failed: error.map(|e| (1, anyhow!(e.to_string()))),
+			entries_in: 3,
+			entries_out: 2,
+			entries_new: 2,
+		};
+
+		// Nothing was dirty.
+		record(
+			&pool,
+			"news",
+			Trigger::Http,
+			Utc::now(),
+			&RunReport::default(),
+		)
+		.await?;
+		assert!(runs(&pool, "news", None, 10).await?.is_empty());
+
+		for _ in 0..3 {
+			record(&pool, "news", Trigger::Schedule, Utc::now(), &ran(None)).await?;
+		}
+		record(
+			&pool,
+			"news",
+			Trigger::WebSub,
+			Utc::now(),
+			&ran(Some("timed out")),
+		)
+		.await?;
+		record(&pool, "other", Trigger::Http, Utc::now(), &ran(None)).await?;
+
+		let page = runs(&pool, "news", None, 2).await?;
+		assert_eq!(page.len(), 2);
+		assert_eq!(page[0].trigger, Trigger::WebSub);
+		assert_eq!(page[0].failed_node, Some(1));
+		assert_eq!(page[0].error.as_deref(), Some("timed out"));
+		assert_eq!((page[1].entries_in, page[1].entries_out), (3, 2));
+
+		let rest = runs(&pool, "news", Some(page[1].id), 10).await?;
+		assert_eq!(rest.len(), 2);
+		assert!(rest.iter().all(|r| r.error.is_none()));
+
+		Ok(())
+	}
+}
@@ -0,0 +1,148 @@
+use std::{fs, ops::Deref, sync::LazyLock, time::Duration};
+
+use anyhow::anyhow;
+use bytes::{Bytes, BytesMut};
+use reqwest::{Certificate, Proxy, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+
+use crate::{config::HttpConfig, metrics};
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+const TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_RESPONSE_SIZE: u64 = 10 * 1024 * 1024;
+
+static DEFAULT: LazyLock<HttpClient> = LazyLock::new(|| HttpClient {
+	client: reqwest::Client::builder()
+		.user_agent(USER_AGENT)
+		.timeout(TIMEOUT)
+		.build()
+		.expect("default HTTP client"),
+	max_response_size: MAX_RESPONSE_SIZE,
+});
+
+/// The client all outbound requests go through, shared by the app and the nodes of its flows.
+#[derive(Clone, Debug)]
+pub struct HttpClient {
+	client: reqwest::Client,
+	max_response_size: u64,
+}
+
+impl HttpClient {
+	pub fn new(config: &HttpConfig) -> anyhow::Result<Self> {
+		let mut builder = reqwest::Client::builder()
+			.user_agent(config.user_agent.as_deref().unwrap_or(USER_AGENT))
+			.timeout(Duration::from_secs(config.timeout))
+			.connect_timeout(Duration::from_secs(config.connect_timeout));
+
+		if let Some(proxy) = &config.proxy {
+			builder = builder.proxy(Proxy::all(proxy.as_str())?);
+		}
+		for path in &config.ca_certificates {
+			for certificate in Certificate::from_pem_bundle(&fs::read(path)?)? {
+				builder = builder.add_root_certificate(certificate);
+			}
+		}
+
+		Ok(Self {
+			client: builder.build()?,
+			max_response_size: config.max_response_size,
+		})
+	}
+
+	/// Wraps an already configured client, e.g. one trusting a test server.
+	pub fn with_client(client: reqwest::Client, max_response_size: u64) -> Self {
+		Self {
+			client,
+			max_response_size,
+		}
+	}
+
+	/// Sends a request, counting it by host and status in the metrics.
+	pub async fn send(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+		let request = request.build()?;
+		let host = request.url().host_str().unwrap_or_default().to_string();
+
+		let response = self.client.execute(request).await;
+		metrics::record_http_request(&host, response.as_ref().ok().map(Response::status));
+
+		response
+	}
+
+	/// Reads the body of `response`, failing once it grows past the maximum response size.
+	pub async fn bytes(&self, mut response: Response) -> anyhow::Result<Bytes> {
+		let url = response.url().clone();
+		let too_large = || {
+			anyhow!(
+				"Response from `{url}` exceeds {} bytes",
+				self.max_response_size
+			)
+		};
+		if response
+			.content_length()
+			.is_some_and(|l| l > self.max_response_size)
+		{
+			return Err(too_large());
+		}
+
+		let mut body = BytesMut::new();
+		while let Some(chunk) = response.chunk().await? {
+			if (body.len() + chunk.len()) as u64 > self.max_response_size {
+				return Err(too_large());
+			}
+			body.extend_from_slice(&chunk);
+		}
+
+		Ok(body.freeze())
+	}
+
+	pub async fn text(&self, response: Response) -> anyhow::Result<String> {
+		Ok(String::from_utf8_lossy(&self.bytes(response).await?).into_owned())
+	}
+
+	pub async fn json<T: DeserializeOwned>(&self, response: Response) -> anyhow::Result<T> {
+		Ok(serde_json::from_slice(&self.bytes(response).await?)?)
+	}
+}
+
+impl Default for HttpClient {
+	fn default() -> Self {
+		DEFAULT.clone()
+	}
+}
+
+impl Deref for HttpClient {
+	type Target = reqwest::Client;
+
+	fn deref(&self) -> &Self::Target {
+		&self.client
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use axum::{routing::get, Router};
+	use tokio::net::TcpListener;
+
+	use super::HttpClient;
+
+	#[tokio::test]
+	pub async fn max_response_size() -> anyhow::Result<()> {
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let url = format!("http://{}/", listener.local_addr()?);
+		tokio::spawn(async move {
+			let router = Router::new().route("/", get(|| async { "0123456789" }));
+			axum::serve(listener, router).await
+		});
+
+		let client = HttpClient::with_client(reqwest::Client::new(), 10);
+		assert_eq!(
+			client.text(client.get(&url).send().await?).await?,
+			"0123456789"
+		);
+
+		let client = HttpClient::with_client(reqwest::Client::new(), 9);
+		assert!(client.bytes(client.get(&url).send().await?).await.is_err());
+
+		Ok(())
+	}
+}
@@ -0,0 +1,539 @@
+//! `WebSub` hub for our own flow feeds, so readers of `/flow/:name` don't have to poll.
+
+use std::{
+	collections::{HashMap, HashSet},
+	time::Duration,
+};
+
+use anyhow::anyhow;
+use atom_syndication::{Feed, Link};
+use axum::{
+	extract::State,
+	http::{header, StatusCode},
+	Form,
+};
+use chrono::Utc;
+use parking_lot::Mutex;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::Deserialize;
+use serde_with::{serde_as, DurationSeconds};
+use sqlx::SqlitePool;
+use url::Url;
+
+use crate::{
+	app::{AppState, FlowHandle},
+	config::config,
+	flow::node::Data,
+	http::HttpClient,
+	route::{self, Visibility},
+	subscriber::websub::router::{XHubSignature, X_HUB_SIGNATURE},
+};
+
+const DEFAULT_LEASE: Duration = Duration::from_hours(240);
+const MIN_LEASE: Duration = Duration::from_hours(1);
+const MAX_LEASE: Duration = Duration::from_hours(720);
+
+/// Topic URL of a flow, or of one of its named outputs.
+pub fn topic(public_url: &Url, name: &str, output: Option<&str>) -> String {
+	match output {
+		Some(output) => format!("{public_url}flow/{name}/{output}"),
+		None => format!("{public_url}flow/{name}"),
+	}
+}
+
+/// Flow name and output of a topic URL.
+fn parse_topic<'a>(public_url: &Url, topic: &'a str) -> Option<(&'a str, Option<&'a str>)> {
+	let path = topic
+		.strip_prefix(public_url.as_str())?
+		.strip_prefix("flow/")?;
+
+	match path.split_once('/') {
+		Some((name, output)) => Some((name, Some(output))),
+		None => Some((path, None)),
+	}
+}
+
+/// Replaces the upstream `hub` and `self` links of a flow feed with ours.
+pub fn advertise(feed: &mut Feed, public_url: &Url, topic: &str) {
+	feed.links.retain(|l| l.rel.ne("hub") && l.rel.ne("self"));
+	feed.links.push(Link {
+		href: format!("{public_url}hub"),
+		rel: "hub".to_string(),
+		..Default::default()
+	});
+	feed.links.push(Link {
+		href: topic.to_string(),
+		rel: "self".to_string(),
+		..Default::default()
+	});
+}
+
+#[serde_as]
+#[derive(Deserialize, Debug)]
+#[serde(tag = "hub.mode", rename_all = "lowercase")]
+pub enum Request {
+	Subscribe {
+		#[serde(rename = "hub.callback")]
+		callback: Url,
+		#[serde(rename = "hub.topic")]
+		topic: String,
+		#[serde_as(as = "Option<DurationSeconds<String>>")]
+		#[serde(rename = "hub.lease_seconds", default)]
+		lease_seconds: Option<Duration>,
+		#[serde(rename = "hub.secret", default)]
+		secret: Option<String>,
+	},
+	Unsubscribe {
+		#[serde(rename = "hub.callback")]
+		callback: Url,
+		#[serde(rename = "hub.topic")]
+		topic: String,
+	},
+}
+
+/// The last delivery to the subscribers of a topic.
+struct Delivery {
+	/// Revision of the output delivered.
+	version: u64,
+	/// Ids of the entries in the output delivered.
+	ids: HashSet<String>,
+}
+
+pub struct WebSubHub {
+	pool: SqlitePool,
+	http: HttpClient,
+	/// Deliveries by flow and output, so unchanged outputs and entries sent already aren't sent
+	/// again.
+	delivered: Mutex<HashMap<String, HashMap<Option<String>, Delivery>>>,
+}
+
+impl WebSubHub {
+	pub fn new(pool: SqlitePool, http: HttpClient) -> Self {
+		Self {
+			pool,
+			http,
+			delivered: Mutex::default(),
+		}
+	}
+
+	/// Verifies the intent of the subscriber, by having it echo a challenge.
+	async fn verify_intent(
+		&self,
+		callback: &Url,
+		mode: &str,
+		topic: &str,
+		lease: Option<Duration>,
+	) -> anyhow::Result<()> {
+		let challenge: String = rand::thread_rng()
+			.sample_iter(Alphanumeric)
+			.take(32)
+			.map(char::from)
+			.collect();
+
+		let mut url = callback.clone();
+		url.query_pairs_mut()
+			.append_pair("hub.mode", mode)
+			.append_pair("hub.topic", topic)
+			.append_pair("hub.challenge", &challenge);
+		if let Some(lease) = lease {
+			url.query_pairs_mut()
+				.append_pair("hub.lease_seconds", &lease.as_secs().to_string());
+		}
+
+		let response = self
+			.http
+			.send(self.http.get(url))
+			.await?
+			.error_for_status()?;
+		let body = self.http.text(response).await?;
+		if body.trim() != challenge {
+			return Err(anyhow!("Challenge not echoed"));
+		}
+
+		Ok(())
+	}
+
+	async fn subscribe(
+		&self,
+		callback: Url,
+		topic: String,
+		flow: String,
+		output: Option<String>,
+		lease: Duration,
+		secret: Option<String>,
+	) -> anyhow::Result<()> {
+		self.verify_intent(&callback, "subscribe", &topic, Some(lease))
+			.await?;
+
+		let callback = callback.to_string();
+		let lease_end = Utc::now() + lease;
+		sqlx::query!(
+			r#"
+			INSERT OR REPLACE INTO hub_subscriptions (callback, topic, flow, output, secret, lease_end)
+			VALUES (?, ?, ?, ?, ?, ?)
+			"#,
+			callback,
+			topic,
+			flow,
+			output,
+			secret,
+			lease_end
+		)
+		.execute(&self.pool)
+		.await?;
+
+		tracing::info!("`{callback}` subscribed to `{topic}`");
+		Ok(())
+	}
+
+	async fn unsubscribe(&self, callback: Url, topic: String) -> anyhow::Result<()> {
+		self.verify_intent(&callback, "unsubscribe", &topic, None)
+			.await?;
+
+		let callback = callback.to_string();
+		sqlx::query!(
+			"DELETE FROM hub_subscriptions WHERE callback = ? AND topic = ?",
+			callback,
+			topic
+		)
+		.execute(&self.pool)
+		.await?;
+
+		tracing::info!("`{callback}` unsubscribed from `{topic}`");
+		Ok(())
+	}
+
+	/// Forgets what was delivered for a flow that is removed or replaced.
+	pub fn forget(&self, name: &str) {
+		self.delivered.lock().remove(name);
+	}
+
+	/// Distributes the flow's latest result to the subscribers of its topics.
+	pub async fn publish(&self, name: &str, flow: &FlowHandle) -> anyhow::Result<()> {
+		match &config().await.public_url {
+			Some(public_url) => self.deliver(public_url, name, flow).await,
+			None => Ok(()),
+		}
+	}
+
+	async fn deliver(&self, public_url: &Url, name: &str, flow: &FlowHandle) -> anyhow::Result<()> {
+		let now = Utc::now();
+		sqlx::query!("DELETE FROM hub_subscriptions WHERE lease_end < ?", now)
+			.execute(&self.pool)
+			.await?;
+
+		let subscriptions = sqlx::query!(
+			"SELECT callback, topic, output, secret FROM hub_subscriptions WHERE flow = ?",
+			name
+		)
+		.fetch_all(&self.pool)
+		.await?;
+
+		let mut bodies: HashMap<String, Option<String>> = HashMap::new();
+		for subscription in subscriptions {
+			let body = bodies.entry(subscription.topic.clone()).or_insert_with(|| {
+				self.news(
+					public_url,
+					name,
+					flow,
+					subscription.output.as_deref(),
+					&subscription.topic,
+				)
+			});
+			let Some(body) = body.clone() else {
+				continue;
+			};
+
+			let mut request = self
+				.http
+				.post(&subscription.callback)
+				.header(header::CONTENT_TYPE, "application/atom+xml")
+				.header(
+					header::LINK,
+					format!(
+						"<{public_url}hub>; rel=\"hub\", <{}>; rel=\"self\"",
+						subscription.topic
+					),
+				);
+			if let Some(secret) = &subscription.secret {
+				let signature = XHubSignature::sha256(secret.as_bytes(), body.as_bytes())?;
+				request = request.header(X_HUB_SIGNATURE, signature.to_string());
+			}
+
+			match self.http.send(request.body(body)).await {
+				Ok(response) if response.status().is_success() => {}
+				Ok(response) => tracing::warn!(
+					"Distributing `{}` to `{}` failed: {}",
+					subscription.topic,
+					subscription.callback,
+					response.status()
+				),
+				Err(err) => tracing::warn!(
+					"Distributing `{}` to `{}` failed: {err}",
+					subscription.topic,
+					subscription.callback
+				),
+			}
+		}
+
+		Ok(())
+	}
+
+	/// The feed of a topic with only the entries not in its last delivery, `None` if the output
+	/// didn't change or has nothing new.
+	fn news(
+		&self,
+		public_url: &Url,
+		name: &str,
+		flow: &FlowHandle,
+		output: Option<&str>,
+		topic: &str,
+	) -> Option<String> {
+		let io = match output {
+			Some(output) => flow.output_io(output),
+			None => flow.result_io(),
+		}?;
+		let (Data::Feed(mut feed), revision) = io.snapshot()? else {
+			return None;
+		};
+
+		let ids = feed.entries.iter().map(|e| e.id.clone()).collect();
+		let mut delivered = self.delivered.lock();
+		let deliveries = delivered.entry(name.to_string()).or_default();
+		let sent = match deliveries.get(&output.map(String::from)) {
+			Some(last) if last.version == revision.version => return None,
+			Some(last) => Some(&last.ids),
+			None => None,
+		};
+		if let Some(sent) = sent {
+			feed.entries.retain(|e| !sent.contains(&e.id));
+		}
+		deliveries.insert(
+			output.map(String::from),
+			Delivery {
+				version: revision.version,
+				ids,
+			},
+		);
+		drop(delivered);
+
+		if feed.entries.is_empty() {
+			return None;
+		}
+
+		advertise(&mut feed, public_url, topic);
+		Some(feed.to_string())
+	}
+}
+
+/// Handles subscription requests, verifying the subscriber's intent in the background.
+pub async fn request(
+	State(state): State<AppState>,
+	Form(request): Form<Request>,
+) -> Result<StatusCode, (StatusCode, String)> {
+	let Some(public_url) = &config().await.public_url else {
+		return Err((StatusCode::NOT_FOUND, "Not found".to_string()));
+	};
+
+	let topic = match &request {
+		Request::Subscribe { topic, .. } | Request::Unsubscribe { topic, .. } => topic,
+	};
+	let Some((name, output)) = parse_topic(public_url, topic) else {
+		return Err((StatusCode::BAD_REQUEST, format!("Unknown topic `{topic}`")));
+	};
+	let (name, output) = (name.to_string(), output.map(String::from));
+
+	let known = state
+		.flows
+		.lock()
+		.await
+		.get(&name)
+		.is_some_and(|flow| output.as_ref().is_none_or(|o| flow.has_output(o)));
+	let visibility = route::visibility(&state.pool, &name)
+		.await
+		.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+	// Private flows are answered like unknown ones, so their names can't be probed.
+	if !known || visibility == Visibility::Private {
+		return Err((StatusCode::BAD_REQUEST, format!("Unknown topic `{topic}`")));
+	}
+
+	if let Request::Subscribe {
+		secret: Some(secret),
+		..
+	} = &request
+	{
+		if secret.len() >= 200 {
+			return Err((
+				StatusCode::BAD_REQUEST,
+				"hub.secret must be shorter than 200 bytes".to_string(),
+			));
+		}
+	}
+
+	tokio::spawn(async move {
+		let hub = &state.web_sub_hub;
+		let result = match request {
+			Request::Subscribe {
+				callback,
+				topic,
+				lease_seconds,
+				secret,
+			} => {
+				let lease = lease_seconds
+					.unwrap_or(DEFAULT_LEASE)
+					.clamp(MIN_LEASE, MAX_LEASE);
+				hub.subscribe(callback, topic, name, output, lease, secret)
+					.await
+			}
+			Request::Unsubscribe { callback, topic } => hub.unsubscribe(callback, topic).await,
+		};
+
+		if let Err(err) = result {
+			tracing::warn!("Hub request failed: {err}");
+		}
+	});
+
+	Ok(StatusCode::ACCEPTED)
+}
+
+#[cfg(test)]
+mod test {
+	use std::sync::Arc;
+
+	use atom_syndication::{Entry, Feed, FixedDateTime};
+	use axum::{routing::post, Router};
+	use parking_lot::Mutex;
+	use serde_json::json;
+	use tokio::net::TcpListener;
+	use url::Url;
+
+	use super::{advertise, parse_topic, topic, WebSubHub};
+	use crate::{
+		app::FlowHandle,
+		flow::{node::Data, FlowBuilder},
+		http::HttpClient,
+	};
+
+	#[test]
+	pub fn topics() {
+		let public_url = Url::parse("https://rss.example.com/").unwrap();
+
+		let first = topic(&public_url, "news", None);
+		assert_eq!(first, "https://rss.example.com/flow/news");
+		assert_eq!(parse_topic(&public_url, &first), Some(("news", None)));
+		assert_eq!(
+			parse_topic(&public_url, "https://rss.example.com/flow/news/first"),
+			Some(("news", Some("first")))
+		);
+		assert_eq!(
+			parse_topic(&public_url, "https://other.example.com/flow/news"),
+			None
+		);
+
+		let mut feed = atom_syndication::Feed::default();
+		feed.links.push(atom_syndication::Link {
+			href: "https://upstream.example.com/hub".to_string(),
+			rel: "hub".to_string(),
+			..Default::default()
+		});
+		advertise(&mut feed, &public_url, &first);
+
+		let links: Vec<_> = feed
+			.links
+			.iter()
+			.map(|l| (l.rel.as_str(), l.href.as_str()))
+			.collect();
+		assert_eq!(
+			links,
+			[
+				("hub", "https://rss.example.com/hub"),
+				("self", "https://rss.example.com/flow/news")
+			]
+		);
+	}
+
+	fn feed(entries: &[(&str, &str)]) -> anyhow::Result<Data> {
+		Ok(Data::Feed(Feed {
+			entries: entries
+				.iter()
+				.map(|(id, updated)| {
+					Ok(Entry {
+						id: (*id).to_string(),
+						updated: FixedDateTime::parse_from_rfc3339(updated)?,
+						..Entry::default()
+					})
+				})
+				.collect::<anyhow::Result<_>>()?,
+			..Feed::default()
+		}))
+	}
+
+	#[tokio::test]
+	pub async fn deliver_news_only() -> anyhow::Result<()> {
+		let pool = crate::test::pool().await?;
+
+		let received = Arc::new(Mutex::new(Vec::new()));
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let callback = format!("http://{}/callback", listener.local_addr()?);
+		let router = Router::new().route(
+			"/callback",
+			post({
+				let received = received.clone();
+				|body: String| async move { received.lock().push(body) }
+			}),
+		);
+		tokio::spawn(async move { axum::serve(listener, router).await });
+
+		let public_url = Url::parse("https://rss.example.com/")?;
+		let topic = topic(&public_url, "news", None);
+		sqlx::query(
+			"INSERT INTO hub_subscriptions (callback, topic, flow, lease_end) VALUES (?, ?, 'news', datetime('now', '+1 day'))",
+		)
+		.bind(&callback)
+		.bind(&topic)
+		.execute(&pool)
+		.await?;
+
+		let flow: FlowBuilder = serde_json::from_value(json!({
+			"nodes": [{ "type": "Feed", "url": "https://example.com/feed.xml", "ttl": 60 }]
+		}))?;
+		let flow = FlowHandle::new(Arc::new(flow.build()?));
+		let result = flow.result_io().expect("flow has a result").clone();
+		let hub = WebSubHub::new(pool, HttpClient::default());
+
+		result.accept(feed(&[("a", "2024-07-01T10:00:00Z")])?)?;
+		hub.deliver(&public_url, "news", &flow).await?;
+		assert_eq!(received.lock().len(), 1);
+
+		// A run that changes nothing posts nothing.
+		result.accept(feed(&[("a", "2024-07-01T10:00:00Z")])?)?;
+		hub.deliver(&public_url, "news", &flow).await?;
+		assert_eq!(received.lock().len(), 1);
+
+		result.accept(feed(&[
+			("b", "2024-07-02T10:00:00Z"),
+			("a", "2024-07-01T10:00:00Z"),
+		])?)?;
+		hub.deliver(&public_url, "news", &flow).await?;
+		assert_eq!(received.lock().len(), 2);
+		assert!(received.lock()[1].contains("<id>b</id>"));
+		assert!(!received.lock()[1].contains("<id>a</id>"));
+
+		// Backdated entries are new all the same, re-dated ones are not.
+		result.accept(feed(&[
+			("b", "2024-07-03T10:00:00Z"),
+			("a", "2024-07-01T10:00:00Z"),
+			("c", "2024-06-01T10:00:00Z"),
+		])?)?;
+		hub.deliver(&public_url, "news", &flow).await?;
+		assert_eq!(received.lock().len(), 3);
+		assert!(received.lock()[2].contains("<id>c</id>"));
+		assert!(!received.lock()[2].contains("<id>b</id>"));
+
+		// A replaced flow starts over.
+		hub.forget("news");
+		assert!(hub.delivered.lock().is_empty());
+
+		Ok(())
+	}
+}
@@ -2,18 +2,31 @@
 #![allow(clippy::module_name_repetitions)]
 use std::net::SocketAddr;
 
+use clap::Parser;
 use tokio::net::TcpListener;
+use tracing_subscriber::EnvFilter;
 
 mod app;
+mod cli;
 mod config;
 mod feed;
 mod flow;
+mod flows_dir;
+mod history;
+mod http;
+mod hub;
+mod metrics;
 mod route;
+mod scheduler;
 mod subscriber;
+#[cfg(test)]
+mod test;
 
 use crate::{
 	app::{app, websub_check},
+	cli::{Cli, Command},
 	config::config,
+	http::HttpClient,
 };
 
 #[global_allocator]
@@ -21,21 +34,38 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-	tracing_subscriber::fmt::init();
+	match Cli::parse().command.unwrap_or(Command::Serve) {
+		Command::Serve => {
+			tracing_subscriber::fmt::init();
+			serve().await
+		}
+		Command::Task(task) => {
+			// Stdout is for the task's output.
+			tracing_subscriber::fmt()
+				.with_env_filter(EnvFilter::from_default_env())
+				.with_writer(std::io::stderr)
+				.init();
+			cli::run(task).await
+		}
+	}
+}
 
+async fn serve() -> anyhow::Result<()> {
 	let config = config().await;
 
+	let http = HttpClient::new(&config.http)?;
+
 	let listener = TcpListener::bind(SocketAddr::new(config.address, config.port)).await?;
 	if let Some(public_url) = &config.public_url {
-		let public_url = public_url.clone();
+		let (http, public_url) = (http.clone(), public_url.clone());
 		tokio::spawn(async move {
-			if let Err(e) = websub_check(&public_url).await {
+			if let Err(e) = websub_check(&http, &public_url).await {
 				tracing::error!("WebSub check failed. The endpoints at `{}` must be publicly accessible to allow WebSub push reception.", public_url.join("/websub/").unwrap());
 				tracing::error!("WebSub check error: {}", e.root_cause());
 			}
 		});
 	}
-	axum::serve(listener, app().await?).await?;
+	axum::serve(listener, app(http).await?).await?;
 
 	Ok(())
 }
@@ -0,0 +1,227 @@
+//! Prometheus metrics, served at `/metrics` without an API key so scrapers need no credentials.
+//!
+//! Flows and nodes are labelled like in tracing spans: by flow name, and by `Node` variant.
+
+use std::{sync::LazyLock, time::Duration};
+
+use axum::{
+	extract::State,
+	http::{header, StatusCode},
+	response::IntoResponse,
+};
+use prometheus::{
+	register_histogram_vec, register_int_counter_vec, register_int_gauge, register_int_gauge_vec,
+	Encoder, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
+};
+
+use crate::{app::AppState, flow::RunReport, history::Trigger, route::internal_error};
+
+static FLOW_RUNS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+	register_int_counter_vec!(
+		"rssflow_flow_runs_total",
+		"Runs of a flow in which at least one node ran",
+		&["flow", "trigger", "outcome"]
+	)
+	.expect("metric registered once")
+});
+
+static FLOW_RUN_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+	register_histogram_vec!(
+		"rssflow_flow_run_duration_seconds",
+		"Duration of flow runs",
+		&["flow"]
+	)
+	.expect("metric registered once")
+});
+
+static NODE_RUN_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+	register_histogram_vec!(
+		"rssflow_node_run_duration_seconds",
+		"Duration of node runs",
+		&["flow", "node"]
+	)
+	.expect("metric registered once")
+});
+
+static NODE_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+	register_int_counter_vec!(
+		"rssflow_node_errors_total",
+		"Node runs that failed",
+		&["flow", "node"]
+	)
+	.expect("metric registered once")
+});
+
+static ENTRIES_EMITTED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+	register_int_counter_vec!(
+		"rssflow_entries_emitted_total",
+		"New entries in the result of successful flow runs, not in the result before the run",
+		&["flow"]
+	)
+	.expect("metric registered once")
+});
+
+static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+	register_int_counter_vec!(
+		"rssflow_http_requests_total",
+		"Outbound HTTP requests, `status` being `error` when no response was received",
+		&["host", "status"]
+	)
+	.expect("metric registered once")
+});
+
+static WEBSUB_PUSHES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+	register_int_counter_vec!(
+		"rssflow_websub_pushes_total",
+		"WebSub pushes received, by signature verification outcome",
+		&["outcome"]
+	)
+	.expect("metric registered once")
+});
+
+static SSE_SUBSCRIBERS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+	register_int_gauge_vec!(
+		"rssflow_sse_subscribers",
+		"Clients subscribed to a flow via SSE",
+		&["flow"]
+	)
+	.expect("metric registered once")
+});
+
+static FLOWS: LazyLock<IntGauge> = LazyLock::new(|| {
+	register_int_gauge!("rssflow_flows", "Loaded flows").expect("metric registered once")
+});
+
+/// Records a run, unless no node ran because none was dirty.
+pub fn record_run(flow: &str, trigger: Trigger, duration: Duration, report: &RunReport) {
+	if report.nodes.is_empty() {
+		return;
+	}
+
+	let outcome = if report.failed.is_some() {
+		"error"
+	} else {
+		"success"
+	};
+	FLOW_RUNS
+		.with_label_values(&[flow, trigger.into(), outcome])
+		.inc();
+	FLOW_RUN_DURATION
+		.with_label_values(&[flow])
+		.observe(duration.as_secs_f64());
+
+	for node in &report.nodes {
+		NODE_RUN_DURATION
+			.with_label_values(&[flow, &node.node])
+			.observe(node.duration.as_secs_f64());
+		if node.error.is_some() {
+			NODE_ERRORS.with_label_values(&[flow, &node.node]).inc();
+		}
+	}
+
+	if report.failed.is_none() {
+		ENTRIES_EMITTED
+			.with_label_values(&[flow])
+			.inc_by(report.entries_new as u64);
+	}
+}
+
+pub fn record_http_request(host: &str, status: Option<StatusCode>) {
+	let status = status.map_or_else(|| "error".to_string(), |s| s.as_u16().to_string());
+	HTTP_REQUESTS.with_label_values(&[host, &status]).inc();
+}
+
+/// `outcome` is one of `accepted`, `unsigned` or `bad_signature`, like the counters of `/api/websub`.
+pub fn record_websub_push(outcome: &str) {
+	WEBSUB_PUSHES.with_label_values(&[outcome]).inc();
+}
+
+pub async fn metrics(
+	State(state): State<AppState>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	{
+		let flows = state.flows.lock().await;
+		FLOWS.set(i64::try_from(flows.len()).unwrap_or(i64::MAX));
+
+		// Flows deleted since the last scrape are dropped.
+		SSE_SUBSCRIBERS.reset();
+		for (name, flow) in flows.iter() {
+			SSE_SUBSCRIBERS
+				.with_label_values(&[name])
+				.set(i64::try_from(flow.tx().receiver_count()).unwrap_or(i64::MAX));
+		}
+	}
+
+	let encoder = TextEncoder::new();
+	let body = encoder
+		.encode_to_string(&prometheus::gather())
+		.map_err(internal_error)?;
+
+	Ok((
+		[(header::CONTENT_TYPE, encoder.format_type().to_string())],
+		body,
+	))
+}
+
+#[cfg(test)]
+mod test {
+	use std::time::Duration;
+
+	use anyhow::anyhow;
+	use axum::http::StatusCode;
+	use prometheus::TextEncoder;
+
+	use super::{record_http_request, record_run};
+	use crate::{
+		flow::{NodeRun, RunReport},
+		history::Trigger,
+	};
+
+	#[test]
+	pub fn metrics() {
+		let node = |error: Option<&str>| NodeRun {
+			index: 0,
+			node: "Feed".to_string(),
+			duration: Duration::from_millis(20),
+			error: error.map(String::from),
+		};
+
+		record_run(
+			"metrics-test",
+			Trigger::Schedule,
+			Duration::from_millis(25),
+			&RunReport {
+				nodes: vec![node(None)],
+				entries_in: 4,
+				entries_out: 5,
+				entries_new: 3,
+				..RunReport::default()
+			},
+		);
+		record_run(
+			"metrics-test",
+			Trigger::WebSub,
+			Duration::from_millis(25),
+			&RunReport {
+				nodes: vec![node(Some("timed out"))],
+				failed: Some((0, anyhow!("timed out"))),
+				..RunReport::default()
+			},
+		);
+		record_http_request("example.com", Some(StatusCode::NOT_MODIFIED));
+
+		let text = TextEncoder::new()
+			.encode_to_string(&prometheus::gather())
+			.unwrap();
+		for line in [
+			r#"rssflow_flow_runs_total{flow="metrics-test",outcome="success",trigger="schedule"} 1"#,
+			r#"rssflow_flow_runs_total{flow="metrics-test",outcome="error",trigger="websub"} 1"#,
+			r#"rssflow_node_errors_total{flow="metrics-test",node="Feed"} 1"#,
+			r#"rssflow_node_run_duration_seconds_count{flow="metrics-test",node="Feed"} 2"#,
+			r#"rssflow_entries_emitted_total{flow="metrics-test"} 3"#,
+			r#"rssflow_http_requests_total{host="example.com",status="304"} 1"#,
+		] {
+			assert!(text.contains(line), "missing `{line}` in:\n{text}");
+		}
+	}
+}
@@ -0,0 +1,171 @@
+//! Per-flow visibility. Private flows are only served with their token, given as `?token=` or as
+//! the password of HTTP Basic auth, whichever the feed reader supports.
+
+use axum::{
+	http::{header, HeaderMap, StatusCode},
+	response::{IntoResponse, Response},
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+	#[default]
+	Public,
+	Private,
+}
+
+#[derive(Deserialize)]
+pub struct TokenQuery {
+	token: Option<String>,
+}
+
+/// A random token, prefixed to tell what it is for.
+pub fn random_token(prefix: &str) -> String {
+	let random: String = rand::thread_rng()
+		.sample_iter(Alphanumeric)
+		.take(40)
+		.map(char::from)
+		.collect();
+
+	format!("{prefix}_{random}")
+}
+
+pub fn hash(token: &str) -> Vec<u8> {
+	Sha256::digest(token.as_bytes()).to_vec()
+}
+
+/// The password of a Basic `Authorization` header, the user name is ignored.
+fn basic_password(headers: &HeaderMap) -> Option<String> {
+	let encoded = headers
+		.get(header::AUTHORIZATION)?
+		.to_str()
+		.ok()?
+		.strip_prefix("Basic ")?;
+	let decoded = String::from_utf8(BASE64_STANDARD.decode(encoded.trim()).ok()?).ok()?;
+
+	Some(match decoded.split_once(':') {
+		Some((_, password)) => password.to_string(),
+		None => decoded,
+	})
+}
+
+/// Visibility of a stored flow, flows not stored in the database being public.
+pub async fn visibility(pool: &SqlitePool, name: &str) -> anyhow::Result<Visibility> {
+	Ok(sqlx::query_scalar!(
+		r#"SELECT visibility as "visibility: Visibility" FROM flows WHERE name = ?"#,
+		name
+	)
+	.fetch_optional(pool)
+	.await?
+	.unwrap_or_default())
+}
+
+/// Checks that the request may read the flow, returning its visibility.
+pub async fn authorize(
+	pool: &SqlitePool,
+	name: &str,
+	query: &TokenQuery,
+	headers: &HeaderMap,
+) -> Result<Visibility, Response> {
+	let record = sqlx::query!(
+		r#"SELECT visibility as "visibility: Visibility", token_hash FROM flows WHERE name = ?"#,
+		name
+	)
+	.fetch_optional(pool)
+	.await
+	.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response())?;
+
+	let Some(record) = record else {
+		return Ok(Visibility::Public);
+	};
+	if record.visibility == Visibility::Public {
+		return Ok(Visibility::Public);
+	}
+
+	let token = query.token.clone().or_else(|| basic_password(headers));
+	match (token, record.token_hash) {
+		(Some(token), Some(token_hash)) if hash(&token) == token_hash => Ok(Visibility::Private),
+		_ => Err(unauthorized()),
+	}
+}
+
+/// Answer to requests for a private flow without its token, and for flows that don't exist, so
+/// the names of private flows can't be probed.
+pub fn unauthorized() -> Response {
+	(
+		StatusCode::UNAUTHORIZED,
+		[(header::WWW_AUTHENTICATE, "Basic realm=\"rssflow\"")],
+		"This flow is private or does not exist".to_string(),
+	)
+		.into_response()
+}
+
+#[cfg(test)]
+mod test {
+	use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+	use base64::{prelude::BASE64_STANDARD, Engine};
+
+	use super::{authorize, basic_password, hash, random_token, TokenQuery, Visibility};
+
+	#[tokio::test]
+	pub async fn private() -> anyhow::Result<()> {
+		let pool = crate::test::pool().await?;
+
+		let token = random_token("rsft");
+		let token_hash = hash(&token);
+		sqlx::query(
+			"INSERT INTO flows (name, content, visibility, token_hash) VALUES ('secret', '{}', 'private', ?)",
+		)
+		.bind(&token_hash)
+		.execute(&pool)
+		.await?;
+
+		let none = TokenQuery { token: None };
+		let query = TokenQuery {
+			token: Some(token.clone()),
+		};
+
+		assert_eq!(
+			authorize(&pool, "other", &none, &HeaderMap::new())
+				.await
+				.ok(),
+			Some(Visibility::Public)
+		);
+		assert_eq!(
+			authorize(&pool, "secret", &none, &HeaderMap::new())
+				.await
+				.err()
+				.map(|r| r.status()),
+			Some(StatusCode::UNAUTHORIZED)
+		);
+		assert_eq!(
+			authorize(&pool, "secret", &query, &HeaderMap::new())
+				.await
+				.ok(),
+			Some(Visibility::Private)
+		);
+
+		// `reader:<token>`
+		let mut headers = HeaderMap::new();
+		headers.insert(
+			header::AUTHORIZATION,
+			HeaderValue::from_str(&format!(
+				"Basic {}",
+				BASE64_STANDARD.encode(format!("reader:{token}"))
+			))?,
+		);
+		assert_eq!(basic_password(&headers), Some(token));
+		assert_eq!(
+			authorize(&pool, "secret", &none, &headers).await.ok(),
+			Some(Visibility::Private)
+		);
+
+		Ok(())
+	}
+}
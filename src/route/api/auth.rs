@@ -0,0 +1,352 @@
+//! API keys, sent as `Authorization: Bearer <key>` or `X-API-Key: <key>`.
+
+use std::time::Duration;
+
+use axum::{
+	extract::{Path, Request, State},
+	http::{header, HeaderMap, Method, StatusCode},
+	middleware::Next,
+	response::{IntoResponse, Response},
+	Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{
+	config::config,
+	route::{
+		access::{hash, random_token},
+		internal_error,
+	},
+};
+
+const X_API_KEY: &str = "x-api-key";
+/// How stale `last_used` may get, so reads don't each take the database's write lock.
+const LAST_USED_PRECISION: Duration = Duration::from_mins(1);
+const NO_KEYS: &str =
+	"No API keys exist, so `/api` is closed. Set `API_KEYS` or run `rssflow key <name>`.";
+
+/// What a key may do, `Write` including `Read`.
+#[derive(
+	sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+	Read,
+	Write,
+}
+
+impl Scope {
+	/// Safe methods only read, anything else writes.
+	fn required(method: &Method) -> Self {
+		if method.is_safe() {
+			Self::Read
+		} else {
+			Self::Write
+		}
+	}
+}
+
+fn presented(headers: &HeaderMap) -> Option<&str> {
+	headers
+		.get(header::AUTHORIZATION)
+		.and_then(|v| v.to_str().ok())
+		.and_then(|v| v.strip_prefix("Bearer "))
+		.or_else(|| headers.get(X_API_KEY).and_then(|v| v.to_str().ok()))
+		.map(str::trim)
+}
+
+/// Whether any key exists, in the config or the database. Without one, `/api` is closed.
+async fn has_keys(pool: &SqlitePool) -> anyhow::Result<bool> {
+	let config = config().await;
+	if !config.api_keys.is_empty() || !config.api_read_keys.is_empty() {
+		return Ok(true);
+	}
+
+	Ok(
+		sqlx::query_scalar!(r#"SELECT EXISTS(SELECT 1 FROM api_keys) as "exists: bool""#)
+			.fetch_one(pool)
+			.await?,
+	)
+}
+
+/// The scope of a key stored in the database, recording its use to [`LAST_USED_PRECISION`].
+async fn stored_scope(pool: &SqlitePool, hash: &[u8]) -> anyhow::Result<Option<Scope>> {
+	let Some(key) = sqlx::query!(
+		r#"SELECT scope as "scope: Scope", last_used as "last_used: DateTime<Utc>" FROM api_keys WHERE hash = ?"#,
+		hash
+	)
+	.fetch_optional(pool)
+	.await?
+	else {
+		return Ok(None);
+	};
+
+	let now = Utc::now();
+	let stale = key.last_used.is_none_or(|t| {
+		(now - t)
+			.to_std()
+			.is_ok_and(|age| age >= LAST_USED_PRECISION)
+	});
+	if stale {
+		sqlx::query!(
+			"UPDATE api_keys SET last_used = ? WHERE hash = ?",
+			now,
+			hash
+		)
+		.execute(pool)
+		.await?;
+	}
+
+	Ok(Some(key.scope))
+}
+
+async fn scope(pool: &SqlitePool, key: &str) -> anyhow::Result<Option<Scope>> {
+	let config = config().await;
+	// Hashes are compared rather than keys, so timing doesn't tell how much of a key matched.
+	let hashed = hash(key);
+	let matches = |keys: &[String]| keys.iter().any(|k| hash(k) == hashed);
+
+	if matches(&config.api_keys) {
+		return Ok(Some(Scope::Write));
+	}
+	if matches(&config.api_read_keys) {
+		return Ok(Some(Scope::Read));
+	}
+
+	stored_scope(pool, &hashed).await
+}
+
+fn unauthorized(message: &str) -> Response {
+	(
+		StatusCode::UNAUTHORIZED,
+		[(header::WWW_AUTHENTICATE, "Bearer")],
+		message.to_string(),
+	)
+		.into_response()
+}
+
+/// Rejects requests without a key of the scope their method needs.
+pub async fn require_key(State(pool): State<SqlitePool>, request: Request, next: Next) -> Response {
+	let required = Scope::required(request.method());
+
+	let granted = match presented(request.headers()) {
+		Some(key) => scope(&pool, key).await,
+		None => Ok(None),
+	};
+
+	match granted {
+		Ok(Some(granted)) if granted >= required => next.run(request).await,
+		Ok(Some(_)) => (
+			StatusCode::FORBIDDEN,
+			"This API key is read-only".to_string(),
+		)
+			.into_response(),
+		Ok(None) if presented(request.headers()).is_some() => unauthorized("Invalid API key"),
+		Ok(None) => match has_keys(&pool).await {
+			Ok(true) => unauthorized("Missing API key"),
+			Ok(false) => unauthorized(NO_KEYS),
+			Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+		},
+		Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+	}
+}
+
+/// Warns when no key exists, as every request to `/api` is rejected then.
+pub async fn warn_if_keyless(pool: &SqlitePool) {
+	if !has_keys(pool).await.unwrap_or(true) {
+		tracing::warn!("{NO_KEYS}");
+	}
+}
+
+/// A stored key, without its hash.
+#[derive(Serialize)]
+struct Key {
+	name: String,
+	scope: Scope,
+	created: DateTime<Utc>,
+	last_used: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct NewKey {
+	name: String,
+	scope: Scope,
+}
+
+/// A key just created, the only time it is shown.
+#[derive(Serialize)]
+struct CreatedKey {
+	name: String,
+	scope: Scope,
+	key: String,
+}
+
+/// Stores a new random key, `None` if the name is taken.
+pub async fn create_key(
+	pool: &SqlitePool,
+	name: &str,
+	scope: Scope,
+) -> anyhow::Result<Option<String>> {
+	let key = random_token("rsf");
+	let hash = hash(&key);
+	let now = Utc::now();
+
+	let inserted = sqlx::query!(
+		r#"
+		INSERT INTO api_keys (name, hash, scope, created)
+		VALUES (?, ?, ?, ?)
+		ON CONFLICT (name) DO NOTHING
+		"#,
+		name,
+		hash,
+		scope as _,
+		now
+	)
+	.execute(pool)
+	.await?
+	.rows_affected();
+
+	Ok((inserted > 0).then_some(key))
+}
+
+pub async fn list(
+	State(pool): State<SqlitePool>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let keys = sqlx::query_as!(
+		Key,
+		r#"
+		SELECT
+			name,
+			scope as "scope: Scope",
+			created as "created: DateTime<Utc>",
+			last_used as "last_used: DateTime<Utc>"
+		FROM api_keys
+		ORDER BY name
+		"#
+	)
+	.fetch_all(&pool)
+	.await
+	.map_err(internal_error)?;
+
+	Ok(Json(keys))
+}
+
+pub async fn create(
+	State(pool): State<SqlitePool>,
+	Json(new): Json<NewKey>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let Some(key) = create_key(&pool, &new.name, new.scope)
+		.await
+		.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?
+	else {
+		return Err((
+			StatusCode::CONFLICT,
+			format!("A key named `{}` already exists", new.name),
+		));
+	};
+
+	Ok((
+		StatusCode::CREATED,
+		Json(CreatedKey {
+			name: new.name,
+			scope: new.scope,
+			key,
+		}),
+	))
+}
+
+pub async fn delete(
+	Path(name): Path<String>,
+	State(pool): State<SqlitePool>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	sqlx::query!("DELETE FROM api_keys WHERE name = ?", name)
+		.execute(&pool)
+		.await
+		.map_err(internal_error)?;
+
+	Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod test {
+	use axum::{
+		http::{Method, StatusCode},
+		middleware,
+		routing::get,
+		Router,
+	};
+	use tokio::net::TcpListener;
+
+	use super::{create_key, require_key, stored_scope, Scope};
+	use crate::route::access::hash;
+
+	#[tokio::test]
+	pub async fn keys() -> anyhow::Result<()> {
+		let pool = crate::test::pool().await?;
+
+		let key = create_key(&pool, "reader", Scope::Read)
+			.await?
+			.expect("name is free");
+		assert!(key.starts_with("rsf_"));
+		assert!(create_key(&pool, "reader", Scope::Write).await?.is_none());
+
+		assert_eq!(stored_scope(&pool, &hash(&key)).await?, Some(Scope::Read));
+		assert_eq!(stored_scope(&pool, &hash("rsf_other")).await?, None);
+
+		let last_used = || {
+			sqlx::query_scalar::<_, Option<String>>(
+				"SELECT last_used FROM api_keys WHERE name = 'reader'",
+			)
+			.fetch_one(&pool)
+		};
+		let first = last_used().await?;
+		assert!(first.is_some());
+
+		// Uses within a minute aren't recorded again.
+		stored_scope(&pool, &hash(&key)).await?;
+		assert_eq!(last_used().await?, first);
+
+		assert_eq!(Scope::required(&Method::GET), Scope::Read);
+		assert_eq!(Scope::required(&Method::PUT), Scope::Write);
+		assert!(Scope::Write >= Scope::Read);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	pub async fn closed_without_keys() -> anyhow::Result<()> {
+		let pool = crate::test::pool().await?;
+
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let base = format!("http://{}", listener.local_addr()?);
+		let router = Router::new()
+			.route("/keys", get(|| async {}).post(|| async {}))
+			.route_layer(middleware::from_fn_with_state(pool.clone(), require_key))
+			.with_state(pool.clone());
+		tokio::spawn(async move { axum::serve(listener, router).await });
+
+		let client = reqwest::Client::new();
+		let url = format!("{base}/keys");
+
+		// Nobody gets in, not even to create the first key.
+		for request in [client.get(&url), client.post(&url)] {
+			let response = request.send().await?;
+			assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+			assert!(response.text().await?.contains("No API keys exist"));
+		}
+
+		let key = create_key(&pool, "cli", Scope::Write)
+			.await?
+			.expect("name is free");
+		let response = client.post(&url).send().await?;
+		assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+		assert_eq!(response.text().await?, "Missing API key");
+		let response = client.post(&url).bearer_auth(&key).send().await?;
+		assert_eq!(response.status(), StatusCode::OK);
+
+		Ok(())
+	}
+}
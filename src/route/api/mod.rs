@@ -1,49 +1,59 @@
-use std::sync::Arc;
-
 use axum::{
 	extract::{Path, State},
 	http::StatusCode,
-	response::IntoResponse,
-	routing::{delete, get, put},
+	response::{IntoResponse, Response},
+	routing::{delete, get, post, put},
 	Json, Router,
 };
 use serde::{Deserialize, Serialize};
-use sqlx::{Acquire, SqliteConnection, SqlitePool};
-use url::Url;
+use sqlx::SqlitePool;
 
-use super::internal_error;
+use super::{internal_error, Visibility};
 use crate::{
-	app::{AppState, FlowHandle},
-	config::config,
-	flow::{node::NodeTrait, FlowBuilder},
-	subscriber::{
-		websub::{WebSub, WebSubSubscriber},
-		Subscriber,
-	},
+	app::AppState,
+	flow::{node::NodeTrait, Context, Flow, FlowBuilder},
 };
 
+mod auth;
+mod nodes;
+mod opml;
+mod preview;
+mod runs;
+mod visibility;
+mod websub;
+
+pub use auth::{create_key, require_key, warn_if_keyless, Scope};
+
 #[derive(Serialize, Deserialize)]
 struct FlowResult {
 	name: String,
 	content: FlowBuilder,
+	#[serde(default)]
+	visibility: Visibility,
+	/// Loaded from the flows directory.
+	#[serde(default)]
+	read_only: bool,
 }
 
 async fn get_flows(
 	State(pool): State<SqlitePool>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
 	let mut conn = pool.acquire().await.map_err(internal_error)?;
-	let results: Vec<_> = sqlx::query!("SELECT name, content FROM flows")
-		.fetch_all(&mut *conn)
-		.await
-		.map_err(internal_error)?
-		.into_iter()
-		.filter_map(|r| {
-			Some(FlowResult {
-				name: r.name,
-				content: serde_json::from_str(&r.content).ok()?,
+	let results: Vec<_> =
+		sqlx::query!(r#"SELECT name, content, visibility as "visibility: Visibility", file IS NOT NULL as "read_only!: bool" FROM flows"#)
+			.fetch_all(&mut *conn)
+			.await
+			.map_err(internal_error)?
+			.into_iter()
+			.filter_map(|r| {
+				Some(FlowResult {
+					name: r.name,
+					content: serde_json::from_str(&r.content).ok()?,
+					visibility: r.visibility,
+					read_only: r.read_only,
+				})
 			})
-		})
-		.collect();
+			.collect();
 
 	Ok(Json(results))
 }
@@ -60,22 +70,63 @@ async fn get_flow(
 	Ok(content)
 }
 
+/// Flows loaded from the flows directory can only be changed by editing their file.
+async fn ensure_writable(pool: &SqlitePool, name: &str) -> Result<(), (StatusCode, String)> {
+	let file = sqlx::query_scalar!("SELECT file FROM flows WHERE name = ?", name)
+		.fetch_optional(pool)
+		.await
+		.map_err(internal_error)?
+		.flatten();
+
+	match file {
+		Some(file) => Err((
+			StatusCode::CONFLICT,
+			format!("`{name}` flow is loaded from `{file}` and read-only"),
+		)),
+		None => Ok(()),
+	}
+}
+
 async fn update_flow(
 	Path(name): Path<String>,
 	State(state): State<AppState>,
 	State(pool): State<SqlitePool>,
 	Json(flow): Json<FlowBuilder>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-	let json = serde_json::to_string(&flow).map_err(internal_error)?;
+) -> Response {
+	if let Err(err) = ensure_writable(&pool, &name).await {
+		return err.into_response();
+	}
+
+	let json = match serde_json::to_string(&flow) {
+		Ok(json) => json,
+		Err(err) => return internal_error(err).into_response(),
+	};
 
-	let flow = flow.build();
+	match flow
+		.context(Context::new(&name, pool.clone(), state.http.clone()))
+		.build()
+	{
+		Ok(flow) => save_flow(&name, &state, &pool, json, flow)
+			.await
+			.into_response(),
+		Err(err) => (StatusCode::UNPROCESSABLE_ENTITY, Json(err)).into_response(),
+	}
+}
+
+async fn save_flow(
+	name: &str,
+	state: &AppState,
+	pool: &SqlitePool,
+	json: String,
+	flow: Flow,
+) -> Result<StatusCode, (StatusCode, String)> {
 	flow.run()
 		.await
 		.map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
 
 	let mut conn = pool.acquire().await.map_err(internal_error)?;
 	let update: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM flows WHERE name = ?)")
-		.bind(&name)
+		.bind(name)
 		.fetch_one(&mut *conn)
 		.await
 		.map_err(internal_error)?;
@@ -100,19 +151,10 @@ async fn update_flow(
 		Ok(StatusCode::CREATED)
 	};
 
-	if flow.has_subscriptions() {
-		state
-			.web_sub_subscriber
-			.register_flow(&name, &flow)
-			.await
-			.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
-	}
-
 	state
-		.flows
-		.lock()
+		.install_flow(name, flow)
 		.await
-		.insert(name.clone(), FlowHandle::new(Arc::new(flow)));
+		.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
 
 	out
 }
@@ -122,19 +164,12 @@ async fn delete_flow(
 	State(state): State<AppState>,
 	State(pool): State<SqlitePool>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-	if let Some(flow) = state.flows.lock().await.remove(&name) {
-		let mut conn = pool.acquire().await.map_err(internal_error)?;
-		sqlx::query!("DELETE FROM flows WHERE name = ?", name)
-			.execute(&mut *conn)
-			.await
-			.map_err(internal_error)?;
+	ensure_writable(&pool, &name).await?;
 
-		state
-			.web_sub_subscriber
-			.unregister_flow(flow)
-			.await
-			.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
-	}
+	state
+		.remove_flow(&name)
+		.await
+		.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
 
 	Ok(StatusCode::NO_CONTENT)
 }
@@ -143,7 +178,19 @@ pub fn router() -> Router<AppState> {
 	Router::new()
 		// .route("/flow", post(create_flow))
 		.route("/flow", get(get_flows))
+		.route("/flow/preview", post(preview::preview))
 		.route("/flow/:name", get(get_flow))
 		.route("/flow/:name", put(update_flow))
 		.route("/flow/:name", delete(delete_flow))
+		.route("/flow/:name/visibility", put(visibility::set))
+		.route("/flow/:name/token", post(visibility::rotate_token))
+		.route("/flow/:name/runs", get(runs::list))
+		.route("/opml", get(opml::export))
+		.route("/opml", post(opml::import))
+		.route("/nodes", get(nodes::list))
+		.route("/schema/flow", get(nodes::flow_schema))
+		.route("/websub", get(websub::list))
+		.route("/keys", get(auth::list))
+		.route("/keys", post(auth::create))
+		.route("/keys/:name", delete(auth::delete))
 }
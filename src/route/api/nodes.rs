@@ -0,0 +1,14 @@
+use axum::Json;
+use schemars::schema::RootSchema;
+
+use crate::flow::catalog::{self, NodeType};
+
+/// Node types compiled into this build, with the JSON Schema of their config.
+pub async fn list() -> Json<Vec<NodeType>> {
+	Json(catalog::nodes())
+}
+
+/// JSON Schema of a flow, as accepted by `PUT /api/flow/:name`.
+pub async fn flow_schema() -> Json<RootSchema> {
+	Json(catalog::flow_schema())
+}
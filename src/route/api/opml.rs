@@ -0,0 +1,423 @@
+//! Subscription lists in OPML, to move feeds between readers and rssflow.
+
+use std::{collections::HashSet, time::Duration};
+
+use axum::{
+	extract::{Query, State},
+	http::{header, StatusCode},
+	response::{IntoResponse, Response},
+	Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationSeconds};
+use sqlx::SqlitePool;
+use url::Url;
+
+use crate::{
+	app::AppState,
+	config::config,
+	flow::{feed::Feed, node::Data, Context, FlowBuilder},
+	hub,
+	route::internal_error,
+};
+
+const DEFAULT_TTL: Duration = Duration::from_hours(1);
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "opml")]
+struct Opml {
+	#[serde(rename = "@version")]
+	version: String,
+	#[serde(default)]
+	head: Head,
+	body: Body,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Head {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	title: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Body {
+	#[serde(default, rename = "outline")]
+	outlines: Vec<Outline>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Outline {
+	#[serde(rename = "@text", skip_serializing_if = "Option::is_none")]
+	text: Option<String>,
+	#[serde(rename = "@title", skip_serializing_if = "Option::is_none")]
+	title: Option<String>,
+	#[serde(rename = "@type", skip_serializing_if = "Option::is_none")]
+	r#type: Option<String>,
+	#[serde(rename = "@xmlUrl", skip_serializing_if = "Option::is_none")]
+	xml_url: Option<String>,
+	/// Categories nest outlines.
+	#[serde(default, rename = "outline", skip_serializing_if = "Vec::is_empty")]
+	outlines: Vec<Outline>,
+}
+
+impl Outline {
+	/// Outlines of feeds, at any depth.
+	fn feeds(&self) -> Box<dyn Iterator<Item = &Outline> + '_> {
+		Box::new(
+			self.xml_url
+				.is_some()
+				.then_some(self)
+				.into_iter()
+				.chain(self.outlines.iter().flat_map(Outline::feeds)),
+		)
+	}
+}
+
+/// Options of an import, the OPML document being the body.
+#[serde_as]
+#[derive(Deserialize)]
+pub struct Import {
+	/// Flow as JSON, whose nodes follow the `Feed` node of every created flow, fed by its output.
+	#[serde(default)]
+	template: Option<String>,
+	/// TTL of the `Feed` nodes, in seconds.
+	#[serde_as(as = "Option<DurationSeconds<String>>")]
+	#[serde(default)]
+	ttl: Option<Duration>,
+}
+
+#[derive(Serialize, Default)]
+struct Imported {
+	created: Vec<Created>,
+	skipped: Vec<Skipped>,
+}
+
+#[derive(Serialize)]
+struct Created {
+	name: String,
+	url: String,
+}
+
+#[derive(Serialize)]
+struct Skipped {
+	url: String,
+	reason: String,
+}
+
+/// Flow name for a feed titled `title`, lowercase words joined by `-`.
+fn slug(title: &str) -> String {
+	let slug = title
+		.split(|c: char| !c.is_alphanumeric())
+		.filter(|word| !word.is_empty())
+		.map(str::to_lowercase)
+		.collect::<Vec<_>>()
+		.join("-");
+
+	if slug.is_empty() {
+		String::from("feed")
+	} else {
+		slug.chars().take(64).collect()
+	}
+}
+
+/// A `Feed` node for `url`, followed by the template's nodes.
+fn feed_flow(
+	url: Url,
+	ttl: Duration,
+	template: Option<&serde_json::Value>,
+) -> Result<FlowBuilder, serde_json::Error> {
+	let flow = FlowBuilder::default().node(Feed::new(url, ttl));
+
+	Ok(match template {
+		Some(template) => flow.then(serde_json::from_value(template.clone())?),
+		None => flow,
+	})
+}
+
+async fn create(
+	state: &AppState,
+	name: &str,
+	flow: Result<FlowBuilder, serde_json::Error>,
+) -> anyhow::Result<()> {
+	let flow = flow?;
+	let json = serde_json::to_string(&flow)?;
+	let flow = flow
+		.context(Context::new(name, state.pool.clone(), state.http.clone()))
+		.build()?;
+
+	sqlx::query!(
+		"INSERT INTO flows (name, content) VALUES (?, ?)",
+		name,
+		json
+	)
+	.execute(&state.pool)
+	.await?;
+
+	state.install_flow(name, flow).await
+}
+
+/// Creates one flow per feed in an OPML document sent as the body, say as `text/x-opml`, named
+/// after its title.
+///
+/// Feeds whose name is taken by a stored flow are skipped, so importing the same document again
+/// creates nothing. Flows are not run here but by the scheduler, as they may be many.
+pub async fn import(
+	State(state): State<AppState>,
+	State(pool): State<SqlitePool>,
+	Query(import): Query<Import>,
+	body: String,
+) -> Response {
+	let opml: Opml = match quick_xml::de::from_str(&body) {
+		Ok(opml) => opml,
+		Err(err) => {
+			return (StatusCode::BAD_REQUEST, format!("Invalid OPML: {err}")).into_response();
+		}
+	};
+	let template: Option<serde_json::Value> = match import
+		.template
+		.as_deref()
+		.map(serde_json::from_str)
+		.transpose()
+	{
+		Ok(template) => template,
+		Err(err) => {
+			return (
+				StatusCode::UNPROCESSABLE_ENTITY,
+				format!("Invalid template: {err}"),
+			)
+				.into_response();
+		}
+	};
+	let ttl = import.ttl.unwrap_or(DEFAULT_TTL);
+
+	// Catch a broken template once, rather than skipping every feed.
+	let example = Url::parse("https://example.com/feed.xml").expect("valid URL");
+	match feed_flow(example, ttl, template.as_ref()).map(FlowBuilder::build) {
+		Ok(Ok(_)) => {}
+		Ok(Err(err)) => return (StatusCode::UNPROCESSABLE_ENTITY, Json(err)).into_response(),
+		Err(err) => {
+			return (
+				StatusCode::UNPROCESSABLE_ENTITY,
+				format!("Invalid template: {err}"),
+			)
+				.into_response();
+		}
+	}
+
+	let existing: HashSet<String> = match sqlx::query_scalar!("SELECT name FROM flows")
+		.fetch_all(&pool)
+		.await
+	{
+		Ok(names) => names.into_iter().collect(),
+		Err(err) => return internal_error(err).into_response(),
+	};
+
+	let mut imported = Imported::default();
+	let mut names = HashSet::new();
+	let mut urls = HashSet::new();
+	let feeds: Vec<&Outline> = opml.body.outlines.iter().flat_map(Outline::feeds).collect();
+	for outline in feeds {
+		let Some(xml_url) = outline.xml_url.clone() else {
+			continue;
+		};
+		let skip = |reason: String| Skipped {
+			url: xml_url.clone(),
+			reason,
+		};
+
+		let url = match Url::parse(&xml_url) {
+			Ok(url) => url,
+			Err(err) => {
+				imported.skipped.push(skip(format!("Invalid URL: {err}")));
+				continue;
+			}
+		};
+		if !urls.insert(url.clone()) {
+			imported.skipped.push(skip(String::from("Listed twice")));
+			continue;
+		}
+
+		let title = outline
+			.title
+			.as_deref()
+			.or(outline.text.as_deref())
+			.or(url.host_str())
+			.unwrap_or_default();
+		let base = slug(title);
+		if existing.contains(&base) {
+			imported
+				.skipped
+				.push(skip(format!("`{base}` flow exists already")));
+			continue;
+		}
+		// Feeds sharing a title within the document get numbered.
+		let mut name = base.clone();
+		for i in 2.. {
+			if !names.contains(&name) && !existing.contains(&name) {
+				break;
+			}
+			name = format!("{base}-{i}");
+		}
+
+		match create(&state, &name, feed_flow(url, ttl, template.as_ref())).await {
+			Ok(()) => {
+				names.insert(name.clone());
+				imported.created.push(Created { name, url: xml_url });
+			}
+			Err(err) => imported.skipped.push(skip(err.to_string())),
+		}
+	}
+
+	let status = if imported.created.is_empty() {
+		StatusCode::OK
+	} else {
+		StatusCode::CREATED
+	};
+	(status, Json(imported)).into_response()
+}
+
+/// Public flows as an OPML document, to subscribe to them in feed readers.
+pub async fn export(
+	State(state): State<AppState>,
+	State(pool): State<SqlitePool>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let Some(public_url) = &config().await.public_url else {
+		return Err((
+			StatusCode::CONFLICT,
+			String::from("`PUBLIC_URL` must be set to export flows"),
+		));
+	};
+
+	// Private flows are left out, their URLs need a token we don't know.
+	let names =
+		sqlx::query_scalar!("SELECT name FROM flows WHERE visibility = 'public' ORDER BY name")
+			.fetch_all(&pool)
+			.await
+			.map_err(internal_error)?;
+
+	let flows = state.flows.lock().await;
+	let outlines = names
+		.into_iter()
+		.filter_map(|name| {
+			let title = match flows.get(&name)?.result() {
+				Some(Data::Feed(feed)) => feed.title.value,
+				_ => name.clone(),
+			};
+
+			Some(Outline {
+				text: Some(title.clone()),
+				title: Some(title),
+				r#type: Some(String::from("rss")),
+				xml_url: Some(hub::topic(public_url, &name, None)),
+				..Outline::default()
+			})
+		})
+		.collect();
+	drop(flows);
+
+	let opml = Opml {
+		version: String::from("2.0"),
+		head: Head {
+			title: Some(String::from("rssflow")),
+		},
+		body: Body { outlines },
+	};
+	let xml = quick_xml::se::to_string(&opml)
+		.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+
+	Ok((
+		[(header::CONTENT_TYPE, "text/x-opml; charset=utf-8")],
+		format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{xml}"),
+	))
+}
+
+#[cfg(test)]
+mod test {
+	use serde_json::json;
+	use url::Url;
+
+	use super::{feed_flow, slug, Body, Head, Opml, Outline, DEFAULT_TTL};
+	use crate::flow::node::NodeTrait;
+
+	#[test]
+	pub fn opml() {
+		let opml: Opml = quick_xml::de::from_str(
+			r#"<?xml version="1.0" encoding="UTF-8"?>
+			<opml version="1.0">
+				<head><title>Subscriptions</title></head>
+				<body>
+					<outline text="Rust Blog" type="rss" xmlUrl="https://blog.rust-lang.org/feed.xml" htmlUrl="https://blog.rust-lang.org/"/>
+					<outline text="News">
+						<outline title="LWN.net" text="LWN" xmlUrl="https://lwn.net/headlines/rss"/>
+						<outline text="Empty category"/>
+					</outline>
+				</body>
+			</opml>"#,
+		)
+		.unwrap();
+
+		let feeds: Vec<_> = opml
+			.body
+			.outlines
+			.iter()
+			.flat_map(Outline::feeds)
+			.map(|o| {
+				(
+					slug(o.title.as_deref().or(o.text.as_deref()).unwrap()),
+					o.xml_url.clone().unwrap(),
+				)
+			})
+			.collect();
+		assert_eq!(
+			feeds,
+			[
+				(
+					"rust-blog".to_string(),
+					"https://blog.rust-lang.org/feed.xml".to_string()
+				),
+				(
+					"lwn-net".to_string(),
+					"https://lwn.net/headlines/rss".to_string()
+				)
+			]
+		);
+		assert_eq!(slug(" -- "), "feed");
+
+		let xml = quick_xml::se::to_string(&Opml {
+			version: "2.0".to_string(),
+			head: Head::default(),
+			body: Body {
+				outlines: vec![Outline {
+					text: Some("news".to_string()),
+					xml_url: Some("https://rss.example.com/flow/news".to_string()),
+					..Outline::default()
+				}],
+			},
+		})
+		.unwrap();
+		assert_eq!(
+			xml,
+			r#"<opml version="2.0"><head/><body><outline text="news" xmlUrl="https://rss.example.com/flow/news"/></body></opml>"#
+		);
+	}
+
+	#[test]
+	pub fn template() -> anyhow::Result<()> {
+		let url = Url::parse("https://example.com/feed.xml")?;
+		let template = json!({
+			"nodes": [{ "type": "Seen" }, { "type": "Sanitise", "field": "Content" }],
+		});
+
+		let flow = feed_flow(url, DEFAULT_TTL, Some(&template))?;
+		assert_eq!(
+			serde_json::to_value(&flow)?["connections"],
+			json!([[[0, 0], [1, 0]], [[1, 0], [2, 0]]])
+		);
+
+		let flow = flow.build()?;
+		assert_eq!(flow.inputs().len(), 1);
+		assert_eq!(flow.outputs().len(), 1);
+
+		Ok(())
+	}
+}
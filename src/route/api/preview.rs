@@ -0,0 +1,170 @@
+use axum::{
+	extract::State,
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use serde::Serialize;
+
+use crate::{
+	flow::{
+		node::{Data, DataKind},
+		Context, FlowBuilder,
+	},
+	http::HttpClient,
+};
+
+#[derive(Serialize)]
+struct Preview {
+	/// The flow's result, as it would be served at `/flow/:name`.
+	result: Option<Data>,
+	error: Option<String>,
+	nodes: Vec<NodePreview>,
+}
+
+#[derive(Serialize)]
+struct NodePreview {
+	index: usize,
+	r#type: String,
+	/// Whether the node ran, it doesn't when an upstream node failed.
+	ran: bool,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	duration_ms: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	error: Option<String>,
+	outputs: Vec<OutputPreview>,
+}
+
+#[derive(Serialize)]
+struct OutputPreview {
+	port: String,
+	kind: Option<DataKind>,
+	entries: Option<usize>,
+}
+
+/// Builds and runs a flow without storing it, reporting what each node produced.
+///
+/// The flow is isolated from stored ones: it has no name, so `Seen` keeps ids in memory and
+/// feeds are fetched unconditionally, and it doesn't subscribe via `WebSub`.
+pub async fn preview(State(http): State<HttpClient>, Json(flow): Json<FlowBuilder>) -> Response {
+	let flow = match flow
+		.context(Context {
+			http,
+			..Context::default()
+		})
+		.build()
+	{
+		Ok(flow) => flow,
+		Err(err) => return (StatusCode::UNPROCESSABLE_ENTITY, Json(err)).into_response(),
+	};
+
+	let report = flow.execute().await;
+	let nodes = flow
+		.node_outputs()
+		.await
+		.into_iter()
+		.enumerate()
+		.map(|(index, (r#type, outputs))| {
+			let run = report.nodes.iter().find(|r| r.index == index);
+
+			NodePreview {
+				index,
+				r#type,
+				ran: run.is_some(),
+				duration_ms: run.map(|r| r.duration.as_secs_f64() * 1000.0),
+				error: run.and_then(|r| r.error.clone()),
+				outputs: outputs
+					.into_iter()
+					.map(|(port, data)| OutputPreview {
+						port: port.to_string(),
+						kind: data.as_ref().map(Data::kind),
+						entries: data.as_ref().map(Data::entry_count),
+					})
+					.collect(),
+			}
+		})
+		.collect();
+
+	Json(Preview {
+		result: flow.result(),
+		error: report.failed.map(|(_, err)| err.to_string()),
+		nodes,
+	})
+	.into_response()
+}
+
+#[cfg(test)]
+mod test {
+	use std::time::Duration;
+
+	use axum::{body::to_bytes, extract::State, http::header, routing::get, Json, Router};
+	use serde_json::Value;
+	use tokio::net::TcpListener;
+
+	use super::preview;
+	use crate::{
+		flow::{feed::Feed, seen::Seen, FlowBuilder},
+		http::HttpClient,
+	};
+
+	const ATOM: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+	<title>Preview</title>
+	<id>urn:preview</id>
+	<updated>2024-07-02T10:00:00Z</updated>
+	<entry><id>urn:a</id><title>A</title><updated>2024-07-02T10:00:00Z</updated></entry>
+	<entry><id>urn:b</id><title>B</title><updated>2024-07-01T10:00:00Z</updated></entry>
+</feed>"#;
+
+	async fn run(flow: FlowBuilder) -> anyhow::Result<Value> {
+		let response = preview(State(HttpClient::default()), Json(flow)).await;
+		Ok(serde_json::from_slice(
+			&to_bytes(response.into_body(), usize::MAX).await?,
+		)?)
+	}
+
+	#[tokio::test]
+	pub async fn snapshot() -> anyhow::Result<()> {
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let url = format!("http://{}/feed", listener.local_addr()?);
+		tokio::spawn(async move {
+			let router = Router::new().route(
+				"/feed",
+				get(|| async { ([(header::CONTENT_TYPE, "application/atom+xml")], ATOM) }),
+			);
+			axum::serve(listener, router).await
+		});
+
+		let preview = run(FlowBuilder::default()
+			.node(Feed::new(url.parse()?, Duration::from_hours(1)))
+			.node(Seen::new())
+			.simple())
+		.await?;
+		assert!(preview["error"].is_null());
+		assert_eq!(preview["nodes"][0]["type"], "Feed");
+		assert_eq!(preview["nodes"][0]["ran"], true);
+		assert_eq!(preview["nodes"][0]["outputs"][0]["port"], "N0P0");
+		assert_eq!(preview["nodes"][0]["outputs"][0]["entries"], 2);
+		assert_eq!(preview["nodes"][1]["outputs"][0]["entries"], 2);
+		assert_eq!(
+			preview["result"]["entries"].as_array().map(Vec::len),
+			Some(2)
+		);
+
+		// Nothing listens on the discard port, so the feed can't be fetched.
+		let preview = run(FlowBuilder::default()
+			.node(Feed::new(
+				"http://127.0.0.1:9/".parse()?,
+				Duration::from_hours(1),
+			))
+			.node(Seen::new())
+			.simple())
+		.await?;
+		assert!(preview["error"].is_string());
+		assert!(preview["nodes"][0]["error"].is_string());
+		assert_eq!(preview["nodes"][1]["ran"], false);
+		assert!(preview["result"].is_null());
+
+		Ok(())
+	}
+}
@@ -0,0 +1,47 @@
+use axum::{
+	extract::{Path, Query, State},
+	http::StatusCode,
+	response::IntoResponse,
+	Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::history::{self, Run};
+
+const DEFAULT_LIMIT: u32 = 50;
+const MAX_LIMIT: u32 = 500;
+
+#[derive(Deserialize)]
+pub struct Page {
+	/// Only runs older than this one, the `next` of the previous page.
+	before: Option<i64>,
+	limit: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct Runs {
+	runs: Vec<Run>,
+	/// Cursor of the next page, if there may be one.
+	next: Option<i64>,
+}
+
+/// Run history of a flow, newest first.
+pub async fn list(
+	Path(name): Path<String>,
+	State(pool): State<SqlitePool>,
+	Query(page): Query<Page>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let limit = page.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+	let runs = history::runs(&pool, &name, page.before, limit)
+		.await
+		.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+
+	let next = if runs.len() == limit as usize {
+		runs.last().map(|r| r.id)
+	} else {
+		None
+	};
+
+	Ok(Json(Runs { runs, next }))
+}
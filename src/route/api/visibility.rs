@@ -0,0 +1,101 @@
+use axum::{
+	extract::{Path, State},
+	http::StatusCode,
+	response::IntoResponse,
+	Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::route::{
+	access::{hash, random_token, visibility, Visibility},
+	internal_error,
+};
+
+#[derive(Deserialize)]
+pub struct SetVisibility {
+	visibility: Visibility,
+}
+
+/// The flow's token, only shown when it is created or rotated.
+#[derive(Serialize)]
+struct Token {
+	visibility: Visibility,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	token: Option<String>,
+}
+
+async fn rotate(pool: &SqlitePool, name: &str) -> Result<String, (StatusCode, String)> {
+	let token = random_token("rsft");
+	let token_hash = hash(&token);
+
+	let updated = sqlx::query!(
+		"UPDATE flows SET token_hash = ? WHERE name = ?",
+		token_hash,
+		name
+	)
+	.execute(pool)
+	.await
+	.map_err(internal_error)?
+	.rows_affected();
+	if updated == 0 {
+		return Err((StatusCode::NOT_FOUND, String::from("Not found")));
+	}
+
+	Ok(token)
+}
+
+/// Makes a flow public or private, creating its token the first time it is made private.
+pub async fn set(
+	Path(name): Path<String>,
+	State(pool): State<SqlitePool>,
+	Json(SetVisibility { visibility }): Json<SetVisibility>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let Some(token_hash) = sqlx::query_scalar!("SELECT token_hash FROM flows WHERE name = ?", name)
+		.fetch_optional(&pool)
+		.await
+		.map_err(internal_error)?
+	else {
+		return Err((StatusCode::NOT_FOUND, String::from("Not found")));
+	};
+
+	sqlx::query!(
+		"UPDATE flows SET visibility = ? WHERE name = ?",
+		visibility as _,
+		name
+	)
+	.execute(&pool)
+	.await
+	.map_err(internal_error)?;
+
+	let mut token = None;
+	if visibility == Visibility::Private {
+		// Subscribers of our hub would keep receiving the flow otherwise.
+		sqlx::query!("DELETE FROM hub_subscriptions WHERE flow = ?", name)
+			.execute(&pool)
+			.await
+			.map_err(internal_error)?;
+
+		if token_hash.is_none() {
+			token = Some(rotate(&pool, &name).await?);
+		}
+	}
+
+	Ok(Json(Token { visibility, token }))
+}
+
+/// Replaces the flow's token, the old one no longer being accepted.
+pub async fn rotate_token(
+	Path(name): Path<String>,
+	State(pool): State<SqlitePool>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let token = rotate(&pool, &name).await?;
+	let visibility = visibility(&pool, &name)
+		.await
+		.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+
+	Ok(Json(Token {
+		visibility,
+		token: Some(token),
+	}))
+}
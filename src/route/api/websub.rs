@@ -0,0 +1,55 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+use crate::{route::internal_error, subscriber::websub::LeaseState};
+
+/// A `WebSub` subscription, without its secret.
+#[derive(Serialize)]
+struct Subscription {
+	topic: String,
+	hub: String,
+	subscribed: bool,
+	state: LeaseState,
+	lease_end: Option<DateTime<Utc>>,
+	next_attempt: Option<DateTime<Utc>>,
+	attempts: i64,
+	last_error: Option<String>,
+
+	/// Pushes with a valid signature.
+	accepted: i64,
+	/// Pushes without an `X-Hub-Signature` header.
+	unsigned: i64,
+	/// Pushes whose signature did not verify.
+	bad_signature: i64,
+}
+
+pub async fn list(
+	State(pool): State<SqlitePool>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let subscriptions = sqlx::query_as!(
+		Subscription,
+		r#"
+		SELECT
+			topic,
+			hub,
+			subscribed,
+			state as "state: LeaseState",
+			lease_end as "lease_end: DateTime<Utc>",
+			next_attempt as "next_attempt: DateTime<Utc>",
+			attempts,
+			last_error,
+			accepted,
+			unsigned,
+			bad_signature
+		FROM websub
+		ORDER BY topic
+		"#
+	)
+	.fetch_all(&pool)
+	.await
+	.map_err(internal_error)?;
+
+	Ok(Json(subscriptions))
+}
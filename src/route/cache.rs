@@ -0,0 +1,214 @@
+//! Rendered flow outputs, so feed readers polling a flow don't re-render it every time.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+	http::{header, HeaderMap, HeaderValue, StatusCode},
+	response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+
+use super::{access::Visibility, format::OutputFormat};
+use crate::flow::node::Revision;
+
+/// An output rendered in one format, for one revision of its data and visibility of its flow,
+/// as only public flows advertise our hub.
+pub struct Rendered {
+	version: u64,
+	format: OutputFormat,
+	visibility: Visibility,
+	body: Bytes,
+	etag: String,
+	last_modified: DateTime<Utc>,
+}
+
+impl Rendered {
+	pub fn new(
+		revision: Revision,
+		format: OutputFormat,
+		visibility: Visibility,
+		body: Bytes,
+	) -> Self {
+		let digest = Sha256::digest(&body);
+
+		Self {
+			version: revision.version,
+			format,
+			visibility,
+			etag: format!("\"{}\"", hex::encode(&digest[..16])),
+			last_modified: revision.modified,
+			body,
+		}
+	}
+
+	/// Whether the client's copy, described by its conditional headers, is still current.
+	fn not_modified(&self, headers: &HeaderMap) -> bool {
+		if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+			return if_none_match.to_str().is_ok_and(|v| {
+				v.split(',')
+					.map(|tag| tag.trim().trim_start_matches("W/"))
+					.any(|tag| tag == "*" || tag == self.etag)
+			});
+		}
+
+		headers
+			.get(header::IF_MODIFIED_SINCE)
+			.and_then(|v| v.to_str().ok())
+			.and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+			.is_some_and(|since| since.timestamp() >= self.last_modified.timestamp())
+	}
+
+	/// The body, or `304 Not Modified` if the request's conditional headers match.
+	pub fn respond(&self, headers: &HeaderMap, cache_control: HeaderValue) -> Response {
+		let validators = [
+			(
+				header::ETAG,
+				HeaderValue::from_str(&self.etag).expect("hex is a valid header value"),
+			),
+			(
+				header::LAST_MODIFIED,
+				HeaderValue::from_str(
+					&self
+						.last_modified
+						.format("%a, %d %b %Y %H:%M:%S GMT")
+						.to_string(),
+				)
+				.expect("dates are valid header values"),
+			),
+			(header::CACHE_CONTROL, cache_control),
+			(header::VARY, HeaderValue::from_static("accept")),
+		];
+
+		if self.not_modified(headers) {
+			return (StatusCode::NOT_MODIFIED, validators).into_response();
+		}
+
+		(
+			validators,
+			[(header::CONTENT_TYPE, self.format.content_type().clone())],
+			self.body.clone(),
+		)
+			.into_response()
+	}
+}
+
+/// Output name (`None` for the result) and format.
+type Key = (Option<String>, OutputFormat);
+
+/// Rendered outputs of a flow.
+#[derive(Default)]
+pub struct RenderCache(Mutex<HashMap<Key, Arc<Rendered>>>);
+
+impl RenderCache {
+	/// The cached rendering, if it is of the given version of the data and visibility of the flow.
+	pub fn get(
+		&self,
+		output: Option<&str>,
+		format: OutputFormat,
+		version: u64,
+		visibility: Visibility,
+	) -> Option<Arc<Rendered>> {
+		self.0
+			.lock()
+			.get(&(output.map(String::from), format))
+			.filter(|r| r.version == version && r.visibility == visibility)
+			.cloned()
+	}
+
+	pub fn insert(&self, output: Option<&str>, rendered: Rendered) -> Arc<Rendered> {
+		let rendered = Arc::new(rendered);
+		self.0.lock().insert(
+			(output.map(String::from), rendered.format),
+			rendered.clone(),
+		);
+		rendered
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+	use chrono::{TimeZone, Utc};
+
+	use super::{RenderCache, Rendered};
+	use crate::{
+		flow::node::Revision,
+		route::{access::Visibility, format::OutputFormat},
+	};
+
+	#[test]
+	pub fn conditional() {
+		let revision = Revision {
+			version: 1,
+			modified: Utc.with_ymd_and_hms(2024, 7, 2, 10, 0, 0).unwrap(),
+		};
+		let cache = RenderCache::default();
+		let rendered = cache.insert(
+			None,
+			Rendered::new(
+				revision,
+				OutputFormat::Atom,
+				Visibility::Public,
+				"<feed/>".into(),
+			),
+		);
+		let get = |output, format, version, visibility| {
+			cache.get(output, format, version, visibility).is_some()
+		};
+		assert!(get(None, OutputFormat::Atom, 1, Visibility::Public));
+		assert!(!get(None, OutputFormat::Atom, 2, Visibility::Public));
+		assert!(!get(None, OutputFormat::Json, 1, Visibility::Public));
+		assert!(!get(
+			Some("first"),
+			OutputFormat::Atom,
+			1,
+			Visibility::Public
+		));
+		// Renderings of a public flow advertise our hub, so aren't served once it is private.
+		assert!(!get(None, OutputFormat::Atom, 1, Visibility::Private));
+
+		let response = rendered.respond(
+			&HeaderMap::new(),
+			HeaderValue::from_static("public, max-age=300"),
+		);
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(
+			response.headers()[header::LAST_MODIFIED],
+			"Tue, 02 Jul 2024 10:00:00 GMT"
+		);
+		assert_eq!(
+			response.headers()[header::CACHE_CONTROL],
+			"public, max-age=300"
+		);
+		let etag = response.headers()[header::ETAG].clone();
+
+		let mut headers = HeaderMap::new();
+		headers.insert(header::IF_NONE_MATCH, etag);
+		let response = rendered.respond(&headers, HeaderValue::from_static("no-cache"));
+		assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+		let mut headers = HeaderMap::new();
+		headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"other\""));
+		assert_eq!(
+			rendered
+				.respond(&headers, HeaderValue::from_static("no-cache"))
+				.status(),
+			StatusCode::OK
+		);
+
+		let mut headers = HeaderMap::new();
+		headers.insert(
+			header::IF_MODIFIED_SINCE,
+			HeaderValue::from_static("Tue, 02 Jul 2024 10:00:00 GMT"),
+		);
+		assert_eq!(
+			rendered
+				.respond(&headers, HeaderValue::from_static("no-cache"))
+				.status(),
+			StatusCode::NOT_MODIFIED
+		);
+	}
+}
@@ -1,9 +1,11 @@
+use std::time::Instant;
+
 use axum::{
-	extract::{Path, State},
-	http::StatusCode,
+	extract::{Path, Query, State},
+	http::{HeaderMap, HeaderValue, StatusCode},
 	response::{
 		sse::{Event, KeepAlive},
-		IntoResponse, Sse,
+		IntoResponse, Response, Sse,
 	},
 	routing::get,
 	Router,
@@ -12,34 +14,130 @@ use futures::Stream;
 use tokio_stream::{wrappers::BroadcastStream, StreamExt};
 
 use crate::{
-	app::AppState,
+	app::{AppState, FlowHandle},
+	config::config,
 	flow::node::{Data, NodeTrait},
-	route::Atom,
+	history::Trigger,
+	hub,
+	route::{
+		access::{self, TokenQuery, Visibility},
+		cache::Rendered,
+		format::OutputFormat,
+	},
 };
 
 async fn run(
 	Path(name): Path<String>,
 	State(state): State<AppState>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-	if let Some(flow) = state.flows.lock().await.get(&name).cloned() {
-		flow.run()
-			.await
-			.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-		let Some(Data::Feed(feed)) = flow.result() else {
-			return Err((StatusCode::INTERNAL_SERVER_ERROR, ":(".to_string()));
-		};
-
-		Ok(Atom(feed))
-	} else {
-		Err((StatusCode::NOT_FOUND, String::from("Not found")))
+	Query(token): Query<TokenQuery>,
+	format: OutputFormat,
+	headers: HeaderMap,
+) -> Response {
+	run_output(&state, &name, None, format, &token, &headers).await
+}
+
+async fn run_named(
+	Path((name, output)): Path<(String, String)>,
+	State(state): State<AppState>,
+	Query(token): Query<TokenQuery>,
+	format: OutputFormat,
+	headers: HeaderMap,
+) -> Response {
+	run_output(&state, &name, Some(&output), format, &token, &headers).await
+}
+
+/// Serves an output of the flow, rendering it only when its data changed since the last request.
+async fn run_output(
+	state: &AppState,
+	name: &str,
+	output: Option<&str>,
+	format: OutputFormat,
+	token: &TokenQuery,
+	headers: &HeaderMap,
+) -> Response {
+	let visibility = match access::authorize(&state.pool, name, token, headers).await {
+		Ok(visibility) => visibility,
+		Err(response) => return response,
+	};
+	let Some(flow) = state.flows.lock().await.get(name).cloned() else {
+		return access::unauthorized();
+	};
+
+	render_output(state, &flow, name, output, format, visibility, headers)
+		.await
+		.unwrap_or_else(IntoResponse::into_response)
+}
+
+async fn render_output(
+	state: &AppState,
+	flow: &FlowHandle,
+	name: &str,
+	output: Option<&str>,
+	format: OutputFormat,
+	visibility: Visibility,
+	headers: &HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+	state
+		.run_flow(name, flow, Trigger::Http)
+		.await
+		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")))?;
+	// Like scheduled runs, so entries fetched for this request also reach subscribers.
+	state.publish(name, flow);
+
+	let io = match output {
+		Some(output) => flow.output_io(output),
+		None => flow.result_io(),
+	}
+	.ok_or_else(|| (StatusCode::NOT_FOUND, String::from("Not found")))?;
+
+	let max_age = flow
+		.next_run()
+		.map(|at| at.saturating_duration_since(Instant::now()))
+		.unwrap_or_default();
+	let cache_control = HeaderValue::from_str(&format!(
+		"{}, max-age={}",
+		match visibility {
+			Visibility::Public => "public",
+			Visibility::Private => "private",
+		},
+		max_age.as_secs()
+	))
+	.expect("numbers are valid header values");
+
+	if let Some(rendered) = io
+		.revision()
+		.and_then(|r| flow.cache().get(output, format, r.version, visibility))
+	{
+		return Ok(rendered.respond(headers, cache_control));
+	}
+
+	let Some((Data::Feed(mut feed), revision)) = io.snapshot() else {
+		return Err((StatusCode::INTERNAL_SERVER_ERROR, ":(".to_string()));
+	};
+
+	// Private flows can't be subscribed to through our hub.
+	if let (Some(public_url), Visibility::Public) = (&config().await.public_url, visibility) {
+		hub::advertise(&mut feed, public_url, &hub::topic(public_url, name, output));
 	}
+
+	let body = format
+		.render(&feed)
+		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+	let rendered = flow
+		.cache()
+		.insert(output, Rendered::new(revision, format, visibility, body));
+
+	Ok(rendered.respond(headers, cache_control))
 }
 
 async fn subscribe(
 	Path(name): Path<String>,
 	State(state): State<AppState>,
-) -> Result<Sse<impl Stream<Item = anyhow::Result<Event>>>, StatusCode> {
+	Query(token): Query<TokenQuery>,
+	headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = anyhow::Result<Event>>>, Response> {
+	access::authorize(&state.pool, &name, &token, &headers).await?;
+
 	let Some((_flow, rx)) = state
 		.flows
 		.lock()
@@ -47,7 +145,7 @@ async fn subscribe(
 		.get(&name)
 		.map(|h| ((*h).clone(), h.subscribe()))
 	else {
-		return Err(StatusCode::NOT_FOUND);
+		return Err(access::unauthorized());
 	};
 
 	let stream = BroadcastStream::new(rx).map(|res| {
@@ -68,4 +166,5 @@ pub fn router() -> Router<AppState> {
 	Router::new()
 		.route("/:name", get(run))
 		.route("/:name/sse", get(subscribe))
+		.route("/:name/:output", get(run_named))
 }
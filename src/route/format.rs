@@ -0,0 +1,142 @@
+use axum::{
+	async_trait,
+	extract::{FromRequestParts, Query},
+	http::{header, request::Parts, HeaderValue, StatusCode},
+};
+use bytes::Bytes;
+use serde::Deserialize;
+
+use crate::feed::{self, json::JsonFeed};
+
+static APPLICATION_ATOM_XML: HeaderValue = HeaderValue::from_static("application/atom+xml");
+static APPLICATION_FEED_JSON: HeaderValue = HeaderValue::from_static("application/feed+json");
+static APPLICATION_RSS_XML: HeaderValue = HeaderValue::from_static("application/rss+xml");
+
+/// Serialization of a flow's output, picked by the `format` query parameter,
+/// else by the `Accept` header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum OutputFormat {
+	#[default]
+	Atom,
+	Json,
+	/// RSS 2.0, see [`crate::feed::to_rss`] for how Atom fields are mapped.
+	Rss,
+}
+
+impl OutputFormat {
+	fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"atom" => Some(Self::Atom),
+			"json" => Some(Self::Json),
+			"rss" => Some(Self::Rss),
+			_ => None,
+		}
+	}
+
+	fn from_mime(mime: &str) -> Option<Self> {
+		match mime {
+			"application/atom+xml" | "application/xml" | "text/xml" => Some(Self::Atom),
+			"application/feed+json" | "application/json" => Some(Self::Json),
+			"application/rss+xml" => Some(Self::Rss),
+			"*/*" | "application/*" => Some(Self::default()),
+			_ => None,
+		}
+	}
+
+	/// Picks the supported media range with the highest `q`, the first one on ties.
+	fn negotiate(accept: &str) -> Option<Self> {
+		let mut best: Option<(Self, f32)> = None;
+
+		for range in accept.split(',') {
+			let mut params = range.split(';').map(str::trim);
+			let Some(format) = params.next().and_then(Self::from_mime) else {
+				continue;
+			};
+
+			let q = params
+				.filter_map(|p| p.strip_prefix("q="))
+				.find_map(|q| q.parse::<f32>().ok())
+				.unwrap_or(1.0);
+
+			if q > 0.0 && best.is_none_or(|(_, b)| q > b) {
+				best = Some((format, q));
+			}
+		}
+
+		best.map(|(format, _)| format)
+	}
+
+	pub fn content_type(self) -> &'static HeaderValue {
+		match self {
+			Self::Atom => &APPLICATION_ATOM_XML,
+			Self::Json => &APPLICATION_FEED_JSON,
+			Self::Rss => &APPLICATION_RSS_XML,
+		}
+	}
+
+	pub fn render(self, feed: &atom_syndication::Feed) -> anyhow::Result<Bytes> {
+		Ok(match self {
+			Self::Atom => feed.to_string().into(),
+			Self::Json => serde_json::to_vec(&JsonFeed::from(feed))?.into(),
+			Self::Rss => feed::to_rss(feed).to_string().into(),
+		})
+	}
+}
+
+#[derive(Deserialize)]
+struct FormatQuery {
+	format: Option<String>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for OutputFormat {
+	type Rejection = (StatusCode, String);
+
+	async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+		let Query(query) = Query::<FormatQuery>::from_request_parts(parts, state)
+			.await
+			.map_err(|e| (StatusCode::BAD_REQUEST, e.body_text()))?;
+
+		if let Some(name) = query.format {
+			return Self::from_name(&name)
+				.ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Unknown format `{name}`")));
+		}
+
+		Ok(parts
+			.headers
+			.get(header::ACCEPT)
+			.and_then(|v| v.to_str().ok())
+			.and_then(Self::negotiate)
+			.unwrap_or_default())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::OutputFormat;
+
+	#[test]
+	pub fn negotiate() {
+		assert_eq!(
+			OutputFormat::negotiate("application/feed+json"),
+			Some(OutputFormat::Json)
+		);
+		assert_eq!(
+			OutputFormat::negotiate("application/json;q=0.5, application/atom+xml;q=0.9"),
+			Some(OutputFormat::Atom)
+		);
+		assert_eq!(
+			OutputFormat::negotiate("text/html, application/json;q=0.1"),
+			Some(OutputFormat::Json)
+		);
+		assert_eq!(
+			OutputFormat::negotiate("text/html, */*;q=0.8"),
+			Some(OutputFormat::Atom)
+		);
+		assert_eq!(
+			OutputFormat::negotiate("application/rss+xml, application/atom+xml;q=0.9"),
+			Some(OutputFormat::Rss)
+		);
+		assert_eq!(OutputFormat::negotiate("text/html"), None);
+	}
+}
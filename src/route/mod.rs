@@ -1,12 +1,14 @@
-use axum::{
-	http::{header, HeaderValue, StatusCode},
-	response::{IntoResponse, Response},
-};
+use axum::http::StatusCode;
 
+mod access;
 mod api;
+mod cache;
 mod flow;
+mod format;
 
-pub use api::router as api;
+pub use access::{visibility, Visibility};
+pub use api::{create_key, require_key, router as api, warn_if_keyless, Scope};
+pub use cache::RenderCache;
 pub use flow::router as flow;
 
 pub(crate) fn internal_error<E>(err: E) -> (StatusCode, String)
@@ -15,16 +17,3 @@ where
 {
 	(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
 }
-
-static APPLICATION_ATOM_XML: HeaderValue = HeaderValue::from_static("application/atom+xml");
-
-struct Atom(atom_syndication::Feed);
-impl IntoResponse for Atom {
-	fn into_response(self) -> Response {
-		(
-			[(header::CONTENT_TYPE, &APPLICATION_ATOM_XML)],
-			self.0.to_string(),
-		)
-			.into_response()
-	}
-}
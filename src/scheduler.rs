@@ -0,0 +1,145 @@
+use std::{
+	collections::HashMap,
+	time::{Duration, Instant},
+};
+
+use rand::Rng;
+use tokio::{
+	task::{self, JoinSet},
+	time::interval,
+};
+
+use crate::{
+	app::{AppState, FlowHandle},
+	flow::node::NodeTrait,
+	history::Trigger,
+};
+
+/// How often flows are checked for being due.
+const TICK: Duration = Duration::from_secs(5);
+/// Upper bound of the random delay added to each run, so flows sharing a TTL don't all fetch at once.
+const MAX_JITTER: Duration = Duration::from_secs(30);
+const MIN_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_BACKOFF: Duration = Duration::from_hours(1);
+
+#[derive(Default)]
+struct Schedule {
+	/// The `next_run` the jitter was picked for.
+	due: Option<Instant>,
+	jitter: Duration,
+	running: bool,
+	failures: u32,
+	retry_at: Option<Instant>,
+}
+
+/// Delay before retrying a flow that failed `failures` times in a row.
+pub(crate) fn backoff(failures: u32) -> Duration {
+	MIN_BACKOFF
+		.saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+		.min(MAX_BACKOFF)
+}
+
+/// Runs flows whenever one of their nodes wants to (see [`NodeTrait::next_run`]),
+/// broadcasting results to subscribers of the flow.
+pub async fn run(state: AppState) {
+	let mut schedules: HashMap<String, Schedule> = HashMap::new();
+	let mut tasks = JoinSet::new();
+	// Flows of the running tasks, to find the schedule of a task that panicked.
+	let mut running: HashMap<task::Id, String> = HashMap::new();
+	let mut interval = interval(TICK);
+
+	loop {
+		tokio::select! {
+			_ = interval.tick() => {
+				let flows: Vec<(String, FlowHandle)> = state
+					.flows
+					.lock()
+					.await
+					.iter()
+					.map(|(name, flow)| (name.clone(), flow.clone()))
+					.collect();
+				schedules.retain(|name, _| flows.iter().any(|(n, _)| n.eq(name)));
+
+				let now = Instant::now();
+				for (name, flow) in flows {
+					let Some(due) = flow.next_run() else {
+						continue;
+					};
+
+					let schedule = schedules.entry(name.clone()).or_default();
+					if schedule.running {
+						continue;
+					}
+					if schedule.due != Some(due) {
+						schedule.due = Some(due);
+						schedule.jitter = rand::thread_rng().gen_range(Duration::ZERO..=MAX_JITTER);
+					}
+
+					let at = (due + schedule.jitter).max(schedule.retry_at.unwrap_or(due));
+					if at <= now {
+						schedule.running = true;
+						let state = state.clone();
+						let task = tasks.spawn({
+							let name = name.clone();
+							async move {
+								let result = state.run_flow(&name, &flow, Trigger::Schedule).await;
+								result.map(|()| flow)
+							}
+						});
+						running.insert(task.id(), name);
+					}
+				}
+			}
+			Some(joined) = tasks.join_next_with_id() => {
+				let (id, result) = match joined {
+					Ok((id, result)) => (id, result),
+					Err(err) => {
+						let name = running.get(&err.id()).map_or("?", String::as_str);
+						tracing::error!("Scheduled run of `{name}` flow panicked: {err}");
+						(err.id(), Err(anyhow::anyhow!("Run panicked: {err}")))
+					}
+				};
+				let Some(name) = running.remove(&id) else {
+					continue;
+				};
+				let Some(schedule) = schedules.get_mut(&name) else {
+					continue;
+				};
+				schedule.running = false;
+
+				match result {
+					Ok(flow) => {
+						schedule.failures = 0;
+						schedule.retry_at = None;
+						state.publish(&name, &flow);
+					}
+					Err(err) => {
+						schedule.failures += 1;
+						let delay = backoff(schedule.failures);
+						schedule.retry_at = Some(Instant::now() + delay);
+						tracing::warn!(
+							"Scheduled run of `{name}` flow failed ({} in a row), retrying in {}s: {err:#}",
+							schedule.failures,
+							delay.as_secs()
+						);
+					}
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::time::Duration;
+
+	use super::{backoff, MAX_BACKOFF};
+
+	#[test]
+	pub fn backoff_doubles() {
+		assert_eq!(backoff(1), Duration::from_secs(30));
+		assert_eq!(backoff(2), Duration::from_mins(1));
+		assert_eq!(backoff(4), Duration::from_mins(4));
+		assert_eq!(backoff(100), MAX_BACKOFF);
+	}
+}
@@ -0,0 +1,229 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::time::interval;
+
+use super::{WebSub, WebSubSubscriber};
+use crate::{config::config, scheduler::backoff};
+
+/// How often subscriptions are checked for being due.
+const TICK: Duration = Duration::from_mins(1);
+/// How long to wait for the hub to verify a subscribe request before sending it again.
+const VERIFY_TIMEOUT: Duration = Duration::from_hours(1);
+
+/// State of a subscription, as recorded in the `websub` table.
+#[derive(sqlx::Type, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum LeaseState {
+	/// A subscribe request was accepted by the hub, which has yet to verify it.
+	Requested,
+	/// Verified by the hub, until `lease_end`.
+	Active,
+	/// The last subscribe request failed, it is retried with backoff.
+	Failed,
+}
+
+/// When to renew a lease of `lease` verified at `now`, leaving a tenth of it as margin.
+pub fn renew_at(now: DateTime<Utc>, lease: Duration) -> DateTime<Utc> {
+	now + lease - lease / 10
+}
+
+impl WebSubSubscriber {
+	/// Renews leases before they end and retries failed subscriptions, forever.
+	pub async fn manage_leases(&self) {
+		if config().await.public_url.is_none() {
+			return;
+		}
+
+		if let Err(err) = self.mark_stale(Utc::now()).await {
+			tracing::error!("Failed checking WebSub leases: {err}");
+		}
+
+		let mut interval = interval(TICK);
+		loop {
+			interval.tick().await;
+
+			let due = match self.due(Utc::now()).await {
+				Ok(due) => due,
+				Err(err) => {
+					tracing::error!("Failed checking WebSub leases: {err}");
+					continue;
+				}
+			};
+
+			for subscription in due {
+				tracing::info!("Renewing subscription to `{}`", subscription.topic);
+				if let Err(err) = self.subscribe(&subscription).await {
+					tracing::warn!(
+						"Renewing subscription to `{}` failed: {err}",
+						subscription.topic
+					);
+				}
+			}
+		}
+	}
+
+	/// Makes subscriptions whose lease ended (e.g. while not running) due right away.
+	async fn mark_stale(&self, now: DateTime<Utc>) -> anyhow::Result<()> {
+		sqlx::query!(
+			r#"
+			UPDATE websub
+			SET next_attempt = ?1
+			WHERE subscribed = 1 AND (lease_end IS NULL OR lease_end < ?1)
+			"#,
+			now
+		)
+		.execute(&self.pool)
+		.await?;
+
+		Ok(())
+	}
+
+	/// Subscriptions still used by a flow that should be (re)subscribed.
+	async fn due(&self, now: DateTime<Utc>) -> anyhow::Result<Vec<WebSub>> {
+		Ok(sqlx::query_as!(
+			WebSub,
+			r#"
+			SELECT topic, hub
+			FROM websub
+			WHERE subscribed = 1
+				AND (next_attempt IS NULL OR next_attempt <= ?)
+				AND EXISTS (
+					SELECT 1
+					FROM websub_flows
+					WHERE websub_flows.topic = websub.topic
+				)
+			"#,
+			now
+		)
+		.fetch_all(&self.pool)
+		.await?)
+	}
+
+	/// Records the outcome of a subscribe request.
+	pub(super) async fn record_attempt(
+		&self,
+		topic: &str,
+		result: &anyhow::Result<()>,
+	) -> anyhow::Result<()> {
+		let now = Utc::now();
+
+		match result {
+			Ok(()) => {
+				let next_attempt = now + VERIFY_TIMEOUT;
+				sqlx::query!(
+					r#"
+					UPDATE websub
+					SET state = ?, subscribed = 1, last_error = NULL, next_attempt = ?
+					WHERE topic = ?
+					"#,
+					LeaseState::Requested as _,
+					next_attempt,
+					topic
+				)
+				.execute(&self.pool)
+				.await?;
+			}
+			Err(err) => {
+				let attempts: i64 =
+					sqlx::query_scalar!("SELECT attempts FROM websub WHERE topic = ?", topic)
+						.fetch_one(&self.pool)
+						.await? + 1;
+				let next_attempt = now + backoff(u32::try_from(attempts).unwrap_or(u32::MAX));
+				let error = err.to_string();
+
+				sqlx::query!(
+					r#"
+					UPDATE websub
+					SET state = ?, subscribed = 1, attempts = ?, last_error = ?, next_attempt = ?
+					WHERE topic = ?
+					"#,
+					LeaseState::Failed as _,
+					attempts,
+					error,
+					next_attempt,
+					topic
+				)
+				.execute(&self.pool)
+				.await?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::time::Duration;
+
+	use anyhow::anyhow;
+	use chrono::Utc;
+
+	use super::{renew_at, LeaseState};
+	use crate::{http::HttpClient, subscriber::websub::WebSubSubscriber};
+
+	#[tokio::test]
+	pub async fn lease() -> anyhow::Result<()> {
+		let pool = crate::test::pool().await?;
+
+		sqlx::query("INSERT INTO flows (name, content) VALUES ('flow', '{}')")
+			.execute(&pool)
+			.await?;
+		sqlx::query(
+			"INSERT INTO websub (uuid, topic, hub, secret) VALUES (x'00', 'topic', 'hub', 'secret')",
+		)
+		.execute(&pool)
+		.await?;
+		sqlx::query("INSERT INTO websub_flows (topic, flow) VALUES ('topic', 'flow')")
+			.execute(&pool)
+			.await?;
+
+		let subscriber = WebSubSubscriber::new(pool.clone(), HttpClient::default());
+		let now = Utc::now();
+
+		// Rows without a lease are stale.
+		subscriber.mark_stale(now).await?;
+		assert_eq!(subscriber.due(now).await?.len(), 1);
+
+		subscriber
+			.record_attempt("topic", &Err(anyhow!("hub unreachable")))
+			.await?;
+		assert!(subscriber.due(Utc::now()).await?.is_empty());
+		assert_eq!(
+			subscriber
+				.due(Utc::now() + Duration::from_secs(31))
+				.await?
+				.len(),
+			1
+		);
+
+		let (state, attempts): (LeaseState, i64) =
+			sqlx::query_as("SELECT state, attempts FROM websub WHERE topic = 'topic'")
+				.fetch_one(&pool)
+				.await?;
+		assert_eq!((state, attempts), (LeaseState::Failed, 1));
+
+		// Failing to subscribe again to an unsubscribed topic still leaves it to be retried.
+		sqlx::query("UPDATE websub SET subscribed = 0")
+			.execute(&pool)
+			.await?;
+		subscriber
+			.record_attempt("topic", &Err(anyhow!("hub unreachable")))
+			.await?;
+		assert_eq!(
+			subscriber
+				.due(Utc::now() + Duration::from_secs(61))
+				.await?
+				.len(),
+			1
+		);
+
+		let lease = Duration::from_hours(240);
+		assert_eq!(renew_at(now, lease), now + Duration::from_hours(216));
+
+		Ok(())
+	}
+}
@@ -8,16 +8,20 @@ use sqlx::{SqliteConnection, SqlitePool};
 use tracing::Instrument;
 use uuid::{NoContext, Timestamp, Uuid};
 
+mod lease;
 pub mod router;
+pub use lease::LeaseState;
 pub use router::router;
 
 use crate::{
 	app::{AppState, FlowHandle},
 	config::config,
 	flow::{
-		node::{Data, DataKind, NodeTrait, IO},
+		node::{DataKind, NodeTrait},
 		Flow,
 	},
+	history::Trigger,
+	http::HttpClient,
 };
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -70,14 +74,21 @@ impl FromStr for WebSub {
 
 pub struct WebSubSubscriber {
 	pool: SqlitePool,
+	http: HttpClient,
 }
 
 impl WebSubSubscriber {
-	pub fn new(pool: SqlitePool) -> Self {
-		Self { pool }
+	pub fn new(pool: SqlitePool, http: HttpClient) -> Self {
+		Self { pool, http }
 	}
 
 	pub async fn register_flow(&self, name: &str, flow: &Flow) -> anyhow::Result<()> {
+		// Hubs can't reach us without it, and the lease manager doesn't run either.
+		if config().await.public_url.is_none() {
+			tracing::warn!("Not subscribing `{name}` to its hubs, PUBLIC_URL is not set");
+			return Ok(());
+		}
+
 		let mut conn = self.pool.acquire().await?;
 		// let mut tx = conn.begin().await?;
 
@@ -86,7 +97,10 @@ impl WebSubSubscriber {
 			.await?;
 
 		for websub in flow.subscriptions() {
-			self.subscribe(&websub).await?;
+			// The attempt is recorded either way, failed ones are retried by the lease manager.
+			if let Err(err) = self.subscribe(&websub).await {
+				tracing::warn!("Subscribing to `{}` failed: {err}", websub.topic);
+			}
 
 			sqlx::query!(
 				"INSERT OR IGNORE INTO websub_flows (topic, flow) VALUES (?, ?)",
@@ -106,7 +120,7 @@ impl WebSubSubscriber {
 	pub async fn unregister_flow(&self, flow: FlowHandle) -> anyhow::Result<()> {
 		if flow.has_subscriptions() {
 			let mut conn = self.pool.acquire().await?;
-			self.remove_unused_subscriptions(&mut conn).await?
+			self.remove_unused_subscriptions(&mut conn).await?;
 		}
 
 		Ok(())
@@ -138,7 +152,7 @@ impl WebSubSubscriber {
 	pub async fn subscribe(&self, subscription: &WebSub) -> anyhow::Result<bool> {
 		let config = config().await;
 		let Some(public_url) = &config.public_url else {
-			return Err(anyhow!(""));
+			return Err(anyhow!("PUBLIC_URL is not set"));
 		};
 
 		let mut conn = self.pool.acquire().await?;
@@ -162,14 +176,6 @@ impl WebSubSubscriber {
 			)
 		};
 
-		let callback = format!("{public_url}websub/{uuid}");
-		let rb = reqwest::Client::new().post(&subscription.hub).form(&[
-			("hub.callback", callback.as_str()),
-			("hub.mode", "subscribe"),
-			("hub.topic", &subscription.topic),
-			("hub.secret", secret.as_str()),
-		]);
-
 		if new_sub {
 			tracing::info!(
 				"Subscribed to `{}` at `{}`",
@@ -188,10 +194,24 @@ impl WebSubSubscriber {
 			.await?;
 		}
 
-		let resp = rb.send().await?;
-		tracing::info!("Response: {}", resp.status());
-		resp.error_for_status()?;
-		Ok(new_sub)
+		let result = async {
+			let callback = format!("{public_url}websub/{uuid}");
+			let rb = self.http.post(&subscription.hub).form(&[
+				("hub.callback", callback.as_str()),
+				("hub.mode", "subscribe"),
+				("hub.topic", &subscription.topic),
+				("hub.secret", secret.as_str()),
+			]);
+
+			let resp = self.http.send(rb).await?;
+			tracing::info!("Response: {}", resp.status());
+			resp.error_for_status()?;
+			Ok(())
+		}
+		.await;
+		self.record_attempt(&subscription.topic, &result).await?;
+
+		result.map(|()| new_sub)
 	}
 
 	pub async fn unsubscribe(&self, subscription: &WebSub) -> anyhow::Result<()> {
@@ -209,7 +229,7 @@ impl WebSubSubscriber {
 		.await?;
 
 		let callback = format!("{public_url}websub/{uuid}");
-		let rb = reqwest::Client::new().post(&subscription.hub).form(&[
+		let rb = self.http.post(&subscription.hub).form(&[
 			("hub.callback", callback.as_str()),
 			("hub.mode", "unsubscribe"),
 			("hub.topic", &subscription.topic),
@@ -219,7 +239,7 @@ impl WebSubSubscriber {
 			.execute(&mut *conn)
 			.await?;
 
-		let resp = rb.send().await?;
+		let resp = self.http.send(rb).await?;
 		tracing::info!("Response: {}", resp.status());
 		resp.error_for_status()?;
 		Ok(())
@@ -248,29 +268,28 @@ impl WebSubSubscriber {
 				return Ok(());
 			};
 
-			// TODO: Proper handling for multiple WebSub-type inputs in one flow
-			if let Some(input) = flow
-				.inputs()
-				.iter()
-				.find(|i| matches!(i.kind(), DataKind::WebSub))
-			{
+			// Prefer the input of the node subscribed to this topic.
+			if let Some(input) = flow.web_sub_input(&subscription.topic).or_else(|| {
+				flow.inputs()
+					.iter()
+					.find(|i| matches!(i.kind(), DataKind::WebSub))
+					.cloned()
+			}) {
 				let _ = input.accept(data.clone());
 
 				let span = tracing::Span::current();
+				let state = state.clone();
 				tokio::spawn(async move {
-					if let Ok(()) = flow.run().instrument(span.clone()).await {
-						let _span = span.entered();
-						if let Some(data) = flow.result() {
-							match data {
-								Data::Feed(feed) => {
-									for entry in feed.entries.into_iter().rev() {
-										let _ = flow.tx().send(Data::Entry(entry));
-									}
-								}
-								_ => {
-									let _ = flow.tx().send(data);
-								}
-							}
+					let result = state
+						.run_flow(&name, &flow, Trigger::WebSub)
+						.instrument(span.clone())
+						.await;
+
+					let _span = span.entered();
+					match result {
+						Ok(()) => state.publish(&name, &flow),
+						Err(err) => {
+							tracing::warn!("Run of `{name}` flow after push failed: {err:#}");
 						}
 					}
 				});
@@ -280,8 +299,3 @@ impl WebSubSubscriber {
 		Ok(())
 	}
 }
-
-// #[async_trait]
-// impl Subscriber<WebSub, Bytes> for WebSubSubscriber {
-//
-// }
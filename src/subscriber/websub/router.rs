@@ -1,4 +1,8 @@
-use std::{str::FromStr, time::Duration};
+use std::{
+	fmt::{Display, Formatter},
+	str::FromStr,
+	time::Duration,
+};
 
 use anyhow::anyhow;
 use axum::{
@@ -16,64 +20,120 @@ use sha2::{Sha256, Sha384, Sha512};
 use sqlx::SqlitePool;
 use uuid::Uuid;
 
+use super::lease;
 use crate::{
 	app::AppState,
-	flow::node::{Data, DataKind, NodeTrait},
+	config::config,
+	metrics,
 	route::internal_error,
-	subscriber::websub::{WebSub, WebSubSubscriber},
+	subscriber::websub::{LeaseState, WebSub},
 };
 
 #[allow(clippy::declare_interior_mutable_const)]
-const X_HUB_SIGNATURE: HeaderName = HeaderName::from_static("x-hub-signature");
+pub(crate) const X_HUB_SIGNATURE: HeaderName = HeaderName::from_static("x-hub-signature");
 
 pub async fn receive(
 	Path(uuid): Path<Uuid>,
-	State(pool): State<SqlitePool>,
 	State(state): State<AppState>,
 	headers: HeaderMap,
 	body: Bytes,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-	let mut conn = pool.acquire().await.map_err(internal_error)?;
+	push(&state, uuid, &headers, body, config().await.websub_strict).await
+}
+
+/// Counts a push by its signature, handing it to the subscribed flows if it verifies.
+async fn push(
+	state: &AppState,
+	uuid: Uuid,
+	headers: &HeaderMap,
+	body: Bytes,
+	strict: bool,
+) -> Result<StatusCode, (StatusCode, String)> {
+	let mut conn = state.pool.acquire().await.map_err(internal_error)?;
 	let record = sqlx::query!("SELECT secret, topic, hub FROM websub WHERE uuid = ?", uuid)
 		.fetch_optional(&mut *conn)
 		.await
 		.map_err(internal_error)?;
 
-	if let Some(record) = record {
-		let signature = headers.get(X_HUB_SIGNATURE);
+	// 410 makes the hub drop a subscription we no longer know of, a 2xx would have it keep
+	// sending.
+	let Some(record) = record else {
+		tracing::warn!("Received WebSub push for unknown subscription `{uuid}`");
+		return Ok(StatusCode::GONE);
+	};
 
-		let Some(signature) = signature
-			.and_then(|v| v.to_str().ok())
-			.and_then(|s| XHubSignature::from_str(s).ok())
-		else {
-			return Ok(StatusCode::OK);
-		};
+	let Some(signature) = headers.get(X_HUB_SIGNATURE) else {
+		sqlx::query!(
+			"UPDATE websub SET unsigned = unsigned + 1 WHERE uuid = ?",
+			uuid
+		)
+		.execute(&mut *conn)
+		.await
+		.map_err(internal_error)?;
 
-		let verified = signature
-			.verify(record.secret.as_bytes(), &body)
-			.map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
-
-		if verified {
-			tracing::info!("Received WebSub push for `{}`", record.topic);
-
-			state
-				.web_sub_subscriber
-				.handle(
-					&state,
-					&WebSub {
-						topic: record.topic,
-						hub: record.hub,
-					},
-					body,
-				)
-				.await
-				.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
-		}
+		metrics::record_websub_push("unsigned");
+		return Ok(reject(strict, &record.topic, "unsigned"));
+	};
+
+	let verified = signature
+		.to_str()
+		.ok()
+		.and_then(|s| XHubSignature::from_str(s).ok())
+		.and_then(|s| s.verify(record.secret.as_bytes(), &body).ok())
+		.unwrap_or_default();
+
+	if !verified {
+		sqlx::query!(
+			"UPDATE websub SET bad_signature = bad_signature + 1 WHERE uuid = ?",
+			uuid
+		)
+		.execute(&mut *conn)
+		.await
+		.map_err(internal_error)?;
+
+		metrics::record_websub_push("bad_signature");
+		return Ok(reject(strict, &record.topic, "bad signature"));
 	}
 
+	sqlx::query!(
+		"UPDATE websub SET accepted = accepted + 1 WHERE uuid = ?",
+		uuid
+	)
+	.execute(&mut *conn)
+	.await
+	.map_err(internal_error)?;
+	drop(conn);
+
+	metrics::record_websub_push("accepted");
+	tracing::info!("Received WebSub push for `{}`", record.topic);
+
+	state
+		.web_sub_subscriber
+		.handle(
+			state,
+			&WebSub {
+				topic: record.topic,
+				hub: record.hub,
+			},
+			body,
+		)
+		.await
+		.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+
 	Ok(StatusCode::OK)
 }
 
+/// Pushes that can't be verified are acknowledged and dropped, unless in strict mode.
+fn reject(strict: bool, topic: &str, reason: &str) -> StatusCode {
+	if strict {
+		tracing::warn!("Rejected WebSub push for `{topic}`: {reason}");
+		StatusCode::FORBIDDEN
+	} else {
+		tracing::debug!("Dropped WebSub push for `{topic}`: {reason}");
+		StatusCode::OK
+	}
+}
+
 #[serde_as]
 #[derive(Deserialize, Debug)]
 #[serde(tag = "hub.mode", rename_all = "lowercase")]
@@ -113,17 +173,26 @@ pub async fn verify(
 				challenge,
 				lease_seconds,
 			} => {
-				let lease_end = Utc::now() + lease_seconds;
-				sqlx::query!(
-					"UPDATE websub SET lease_end = ? WHERE topic = ?",
-					lease_end,
-					record.topic
-				)
-				.execute(&mut *conn)
-				.await
-				.map_err(internal_error)?;
-
 				if record.subscribed && topic.eq(&record.topic) {
+					let now = Utc::now();
+					let lease_end = now + lease_seconds;
+					let next_attempt = lease::renew_at(now, lease_seconds);
+					sqlx::query!(
+						r#"
+						UPDATE websub
+						SET lease_end = ?, state = ?, attempts = 0, last_error = NULL, next_attempt = ?
+						WHERE topic = ?
+						"#,
+						lease_end,
+						LeaseState::Active as _,
+						next_attempt,
+						record.topic
+					)
+					.execute(&mut *conn)
+					.await
+					.map_err(internal_error)?;
+
+					tracing::info!("Subscription to `{}` verified", record.topic);
 					Ok((StatusCode::OK, challenge))
 				} else {
 					Err((StatusCode::BAD_REQUEST, "Bad request".to_string()))
@@ -162,6 +231,14 @@ pub struct XHubSignature {
 }
 
 impl XHubSignature {
+	/// Signs `message`, for distributing content to our own subscribers.
+	pub fn sha256(secret: &[u8], message: &[u8]) -> anyhow::Result<Self> {
+		Ok(XHubSignature {
+			method: "sha256".to_string(),
+			signature: mac::hmac::<Sha256>(secret, message)?,
+		})
+	}
+
 	#[tracing::instrument(skip(secret, message))]
 	pub fn verify(&self, secret: &[u8], message: &[u8]) -> anyhow::Result<bool> {
 		Ok(match self.method.as_str() {
@@ -198,6 +275,12 @@ impl FromStr for XHubSignature {
 	}
 }
 
+impl Display for XHubSignature {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}={}", self.method, hex::encode(&self.signature))
+	}
+}
+
 mod mac {
 	use hmac::{
 		digest::{
@@ -226,4 +309,95 @@ mod mac {
 		hmac.update(message);
 		Ok(hmac.verify_slice(signature).is_ok())
 	}
+
+	pub fn hmac<D>(secret: &[u8], message: &[u8]) -> anyhow::Result<Vec<u8>>
+	where
+		D: CoreProxy,
+		D::Core: HashMarker
+			+ UpdateCore
+			+ FixedOutputCore
+			+ BufferKindUser<BufferKind = Eager>
+			+ Default
+			+ Clone,
+		<D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+		Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+	{
+		let mut hmac: Hmac<D> = Hmac::new_from_slice(secret)?;
+		hmac.update(message);
+		Ok(hmac.finalize().into_bytes().to_vec())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::{collections::HashMap, str::FromStr};
+
+	use axum::{
+		body::Bytes,
+		http::{HeaderMap, StatusCode},
+	};
+	use uuid::Uuid;
+
+	use super::{push, XHubSignature, X_HUB_SIGNATURE};
+	use crate::{app::AppState, http::HttpClient};
+
+	#[test]
+	pub fn sign_and_verify() -> anyhow::Result<()> {
+		let signature = XHubSignature::sha256(b"secret", b"message")?.to_string();
+		assert!(signature.starts_with("sha256="));
+
+		let signature = XHubSignature::from_str(&signature)?;
+		assert!(signature.verify(b"secret", b"message")?);
+		assert!(!signature.verify(b"other", b"message")?);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	pub async fn receive() -> anyhow::Result<()> {
+		let pool = crate::test::pool().await?;
+		let uuid = Uuid::from_u128(1);
+		sqlx::query(
+			"INSERT INTO websub (uuid, topic, hub, secret) VALUES (?, 'https://example.com/feed', 'https://hub.example.com/', 'secret')",
+		)
+		.bind(uuid)
+		.execute(&pool)
+		.await?;
+		let state = AppState::new(HashMap::new(), pool.clone(), HttpClient::default());
+
+		let body = Bytes::from_static(b"<feed/>");
+		let signed = |secret: &[u8]| -> anyhow::Result<HeaderMap> {
+			let signature = XHubSignature::sha256(secret, &body)?;
+			Ok(HeaderMap::from_iter([(
+				X_HUB_SIGNATURE,
+				signature.to_string().parse()?,
+			)]))
+		};
+		let unsigned = HeaderMap::new();
+		let bad = signed(b"other")?;
+		let valid = signed(b"secret")?;
+
+		// Unverifiable pushes are acknowledged by default, and refused in strict mode.
+		for (strict, dropped) in [(false, StatusCode::OK), (true, StatusCode::FORBIDDEN)] {
+			let receive = |headers| push(&state, uuid, headers, body.clone(), strict);
+			assert_eq!(receive(&unsigned).await, Ok(dropped));
+			assert_eq!(receive(&bad).await, Ok(dropped));
+			assert_eq!(receive(&valid).await, Ok(StatusCode::OK));
+		}
+
+		let counts: (i64, i64, i64) =
+			sqlx::query_as("SELECT unsigned, bad_signature, accepted FROM websub WHERE uuid = ?")
+				.bind(uuid)
+				.fetch_one(&pool)
+				.await?;
+		assert_eq!(counts, (2, 2, 2));
+
+		// Pushes for unknown subscriptions are refused as gone, so hubs stop sending them.
+		assert_eq!(
+			push(&state, Uuid::from_u128(2), &valid, body.clone(), true).await,
+			Ok(StatusCode::GONE)
+		);
+
+		Ok(())
+	}
 }
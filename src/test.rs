@@ -0,0 +1,14 @@
+//! Fixtures shared by tests.
+
+use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+
+/// A migrated in-memory database. One connection, as each would open a database of its own.
+pub async fn pool() -> anyhow::Result<SqlitePool> {
+	let pool = SqlitePoolOptions::new()
+		.max_connections(1)
+		.connect("sqlite::memory:")
+		.await?;
+	sqlx::migrate!().run(&pool).await?;
+
+	Ok(pool)
+}